@@ -0,0 +1,102 @@
+//! Pair tests and step time of the collision broad phase with many asteroids and bullets.
+//!
+//! The arena keeps its size, so bodies get denser as their count goes up. Both the naive
+//! and the grid pair tests then grow with the square of the count; what the grid buys is
+//! testing only the share of pairs in neighbouring cells. The cell size, and with it that
+//! share, follows the biggest asteroid stage of the config whatever stage is tested, only
+//! growing a little with the drawn outlines of the big asteroids.
+//!
+//! Run with `cargo bench --bench collision`.
+
+use asteroids::{arena, cfg, config::Config, entity, rng::Rng, Game};
+use macroquad::math;
+use std::{collections::HashSet, time::Instant};
+
+const STEPS: u32 = 20;
+
+fn main() {
+    println!(
+        "{:>6} {:>6} {:>9} {:>12} {:>10} {:>11} {:>14} {:>12} {:>13}",
+        "bodies",
+        "stage",
+        "cell size",
+        "naive pairs",
+        "grid pairs",
+        "grid share",
+        "pairs per body",
+        "circles, ms",
+        "polygons, ms"
+    );
+    for stage in [0, Config::default().asteroids.stages.len() - 1] {
+        for count in [250, 500, 1000, 2000, 4000] {
+            let game = stress_game(count, stage, true);
+            let naive_pairs = game.bullets.len() * game.asteroids.len();
+            let grid_pairs = grid_pairs(&game);
+            println!(
+                "{:>6} {:>6} {:>9.1} {:>12} {:>10} {:>10.1}% {:>14.1} {:>12.3} {:>13.3}",
+                count,
+                stage,
+                game.collision_cell_size(),
+                naive_pairs,
+                grid_pairs,
+                grid_pairs as f32 / naive_pairs as f32 * 100.0,
+                grid_pairs as f32 / count as f32,
+                step_time(count, stage, false) * 1000.0,
+                step_time(count, stage, true) * 1000.0,
+            );
+        }
+    }
+}
+
+/// A running level with `count` asteroids of `stage` and as many ship bullets, all of them
+/// a step into their flight.
+fn stress_game(count: usize, stage: usize, polygons: bool) -> Game {
+    let mut config = Config::default();
+    config.collisions.polygons = polygons;
+    let mut game = Game::new(1, config);
+    let mut rng = Rng::new(2);
+    let random_position = |rng: &mut Rng| {
+        math::vec2(rng.gen_range(0.0, cfg::ARENA_WIDTH), rng.gen_range(0.0, cfg::ARENA_HEIGHT))
+    };
+    game.state = entity::GameState::LevelRunning;
+    // scores the bullets' hits
+    game.players.push(entity::Player::new(0, &game.config));
+    for _ in 0..count {
+        let position = random_position(&mut rng);
+        let mut asteroid = entity::Asteroid::new(position, stage, &mut rng, &game.config);
+        asteroid.prev_position = position - asteroid.body.velocity * cfg::TIME_STEP;
+        game.asteroids.push(asteroid);
+        let position = random_position(&mut rng);
+        let angle = rng.gen_range(0.0, 2.0 * std::f32::consts::PI);
+        let mut bullet =
+            entity::Bullet::new(position, angle, entity::Owner::Player(0), &game.config);
+        bullet.prev_position = position - bullet.body.velocity * cfg::TIME_STEP;
+        game.bullets.push(bullet);
+    }
+    game
+}
+
+/// Pairs the collision system tests between bullets and asteroids, in grids of the cell
+/// size it picks for the game.
+fn grid_pairs(game: &Game) -> usize {
+    let mut grid = arena::Grid::new(game.collision_cell_size());
+    for (i, asteroid) in game.asteroids.iter().enumerate() {
+        grid.insert(i, asteroid.position);
+    }
+    game.bullets
+        .iter()
+        .map(|bullet| grid.near(bullet.position).count())
+        .sum()
+}
+
+/// Mean time of the first steps, hits included.
+fn step_time(count: usize, stage: usize, polygons: bool) -> f64 {
+    let mut total = 0.0;
+    for _ in 0..STEPS {
+        let mut game = stress_game(count, stage, polygons);
+        let start = Instant::now();
+        game.step(&HashSet::new());
+        total += start.elapsed().as_secs_f64();
+    }
+    total / STEPS as f64
+}
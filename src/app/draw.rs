@@ -0,0 +1,419 @@
+use crate::{
+    arena, cfg, entity, entity::cmpt, keymap, netplay, palette, pointer, render, settings,
+};
+use macroquad::{camera, color, material, math, rand, shapes, text, texture, time, window};
+use std::f32::consts::PI;
+
+pub fn update(app: &mut crate::App, _dt: f32) {
+    let game = &app.game;
+    let renderer = &mut app.renderer;
+    let alpha = app.step_accumulator / cfg::TIME_STEP;
+    camera::set_camera(&renderer.canvas.camera);
+    window::clear_background(palette::BLACK);
+    app.star_bg
+        .static_emitter
+        .draw(math::vec2(cfg::ARENA_WIDTH / 2.0, cfg::ARENA_HEIGHT / 2.0));
+    app.star_bg.side_emitter.draw(app.star_bg.side_emitter_pos);
+    renderer
+        .explosion_emitters
+        .retain(|id, _| game.explosions.iter().any(|e| e.id == *id));
+    for explosion in &game.explosions {
+        renderer
+            .explosion_emitters
+            .entry(explosion.id)
+            .or_insert_with(|| render::explosion_emitter(explosion.kind))
+            .draw(interpolate(explosion.prev_position, explosion.position, alpha));
+    }
+    for y in -1..=1 {
+        for x in -1..=1 {
+            let offset = math::vec2(x as f32 * cfg::ARENA_WIDTH, y as f32 * cfg::ARENA_HEIGHT);
+            for entity::Ship {
+                position,
+                prev_position,
+                sprite,
+                has_exhaust,
+                invulnerability_timer,
+                is_in_hyperspace,
+                is_shielded,
+                ..
+            } in game.ships()
+            {
+                let is_blinked_out = (*invulnerability_timer * 8.0) as i32 % 2 == 1;
+                let position = interpolate(*prev_position, *position, alpha) + offset;
+                if !is_blinked_out && !is_in_hyperspace {
+                    draw_ship(position, sprite, *has_exhaust, *is_shielded);
+                }
+            }
+            for entity::Asteroid { position, prev_position, sprite, .. } in &game.asteroids {
+                let layers = match &sprite.variant {
+                    cmpt::SpriteVariant::Vector { layers } => layers,
+                    _ => unreachable!(),
+                };
+                let mut position = interpolate(*prev_position, *position, alpha) + offset;
+                position.x = position.x as i32 as f32;
+                position.y = position.y as i32 as f32;
+                draw_layers(layers, position, sprite.angle);
+            }
+            for entity::Alien { position, prev_position, sprite, .. } in &game.aliens {
+                let position = interpolate(*prev_position, *position, alpha);
+                let angle_by_x = f32::min((position.x * 8.0) % 180.0, 90.0);
+                let layers = match &sprite.variant {
+                    cmpt::SpriteVariant::Vector { layers } => layers,
+                    _ => unreachable!(),
+                };
+                let position = math::vec2(
+                    position.x as i32 as f32 + 0.5,
+                    (position.y + offset.y) as i32 as f32 + 0.5,
+                );
+                draw_layers(&layers[..2], position, angle_by_x.to_radians());
+                draw_layers(&layers[2..], position, 0.0);
+            }
+            for entity::Bullet { position, prev_position, sprite, .. } in &game.bullets {
+                let position = interpolate(*prev_position, *position, alpha) + offset;
+                let color = match sprite.variant {
+                    cmpt::SpriteVariant::Bullet { color } => color,
+                    _ => unreachable!(),
+                };
+                shapes::draw_line(
+                    position.x,
+                    position.y,
+                    position.x + sprite.angle.cos() * cfg::BULLET_LENGTH,
+                    position.y + sprite.angle.sin() * cfg::BULLET_LENGTH,
+                    2.0,
+                    color,
+                );
+            }
+        }
+    }
+    match &app.controls_screen {
+        Some(screen) => draw_controls(screen, app.settings.keymaps()[screen.player_id]),
+        None => draw_hud(game),
+    }
+    if app.pointer.as_ref().is_some_and(|pointer| pointer.is_touch) {
+        draw_touch_buttons();
+    }
+    if let Some(err) = &app.netplay_error {
+        draw_centered_text("NETWORK GAME STOPPED", 48.0, 12, palette::RED);
+        draw_centered_text(&err.to_uppercase(), 64.0, 12, palette::LIGHTGRAY);
+    } else if let Some(connecting) = &app.netplay_connecting {
+        let text = if connecting.is_host() {
+            "WAITING FOR A PLAYER TO JOIN"
+        } else {
+            "JOINING THE HOST"
+        };
+        draw_centered_text(text, 48.0, 12, palette::LIGHTGRAY);
+    } else if app
+        .netplay
+        .as_ref()
+        .is_some_and(|session| session.is_waiting())
+    {
+        draw_centered_text("WAITING FOR THE OTHER PLAYER", 48.0, 12, palette::LIGHTGRAY);
+    }
+    camera::set_default_camera();
+    window::clear_background(palette::BLACK);
+    material::gl_use_material(renderer.crt_effect.unwrap());
+    let letterbox = render::Letterbox::of_window();
+    texture::draw_texture_ex(
+        *renderer.canvas.get_texture(),
+        letterbox.offset.x,
+        letterbox.offset.y,
+        palette::WHITE,
+        texture::DrawTextureParams {
+            dest_size: Some(letterbox.canvas_size()),
+            ..Default::default()
+        },
+    );
+    // game.renderer.canvas.draw();
+    material::gl_use_default_material();
+    if renderer.show_debug_info {
+        let color = palette::DARKGRAY;
+        let ship = game.ships().next();
+        [
+            ("fps", time::get_fps() as f32),
+            ("ship.sprite.angle", ship.map_or(0.0, |sh| sh.sprite.angle)),
+            ("ship.position.x", ship.map_or(0.0, |sh| sh.position.x)),
+            ("ship.position.y", ship.map_or(0.0, |sh| sh.position.y)),
+            ("ship.body.velocity.x", ship.map_or(0.0, |sh| sh.body.velocity.x)),
+            ("ship.body.velocity.y", ship.map_or(0.0, |sh| sh.body.velocity.y)),
+            ("bullet_0.position.x", game.bullets.first().map_or(0.0, |b| b.position.x)),
+            ("bullet_0.position.y", game.bullets.first().map_or(0.0, |b| b.position.y)),
+            ("expl_0.position.x", game.explosions.first().map_or(0.0, |e| e.position.x)),
+            ("expl_0.position.y", game.explosions.first().map_or(0.0, |e| e.position.y)),
+            ("alien_0.position.x", game.aliens.first().map_or(0.0, |a| a.position.x)),
+            ("alien_0.position.y", game.aliens.first().map_or(0.0, |a| a.position.y)),
+        ]
+        .iter()
+        .enumerate()
+        .for_each(|(i, (name, val))| {
+            text::draw_text(&format!("{}: {}", name, val), 0.0, 16.0 * (i + 1) as f32, 16.0, color)
+        });
+        if let Some(session) = &app.netplay {
+            draw_netplay_info(session, 16.0 * 13.0);
+        }
+    }
+}
+
+/// The checksums of both sides are compared once a second, a desync shows up in red.
+fn draw_netplay_info(session: &netplay::Session, y: f32) {
+    let color = palette::DARKGRAY;
+    let checksum = match session.checksum() {
+        Some((step, checksum)) => format!("{:016x} at step {}", checksum, step),
+        None => "none yet".to_string(),
+    };
+    [
+        format!("net.local_player: {}", session.local_player),
+        format!("net.step: {}", session.step()),
+        format!("net.confirmed_step: {}", session.confirmed_step()),
+        format!("net.checksum: {}", checksum),
+    ]
+    .iter()
+    .enumerate()
+    .for_each(|(i, line)| text::draw_text(line, 0.0, y + 16.0 * i as f32, 16.0, color));
+    if let Some(step) = session.desync_step() {
+        let line = format!("DESYNC AT STEP {}", step);
+        text::draw_text(&line, 0.0, y + 16.0 * 4.0, 16.0, palette::RED);
+    }
+}
+
+fn draw_hud(game: &asteroids::Game) {
+    if let entity::GameState::MatchOver = game.state {
+        draw_results(game);
+        return;
+    }
+    for player in &game.players {
+        draw_player_hud(player, game);
+    }
+    let banner = match game.state {
+        entity::GameState::LevelCompleted => Some(format!("WAVE {}", game.wave)),
+        entity::GameState::RoundStarting => {
+            let round = game.players.iter().map(|p| p.rounds_won).sum::<u32>() + 1;
+            Some(format!("ROUND {}", round))
+        }
+        entity::GameState::RoundOver => {
+            let mut winners = game
+                .players
+                .iter()
+                .filter(|p| p.score >= game.config.versus.target_score);
+            match (winners.next(), winners.next()) {
+                (Some(winner), None) => Some(format!("PLAYER {} TAKES THE ROUND", winner.id + 1)),
+                _ => Some("A DRAW, THE ROUND IS REPLAYED".to_string()),
+            }
+        }
+        _ => None,
+    };
+    if let Some(banner) = banner {
+        draw_centered_text(&banner, cfg::ARENA_HEIGHT / 2.0, 16, palette::WHITE);
+    }
+    if game.mode.versus {
+        let target = format!("FIRST TO {}", game.config.versus.target_score);
+        draw_centered_text(&target, 12.0, 12, palette::DARKGRAY);
+    }
+    if let entity::GameState::Pause = game.state {
+        draw_centered_text("F1 CONTROLS", cfg::ARENA_HEIGHT - 8.0, 12, palette::DARKGRAY);
+    }
+}
+
+/// The versus match results, with the winner on top.
+fn draw_results(game: &asteroids::Game) {
+    let mut players: Vec<_> = game.players.iter().collect();
+    players.sort_by_key(|p| std::cmp::Reverse((p.rounds_won, p.kills)));
+    if let Some(winner) = players.first() {
+        let title = format!("PLAYER {} WINS THE MATCH", winner.id + 1);
+        draw_centered_text(&title, 48.0, 16, palette::WHITE);
+    }
+    for (i, player) in players.iter().enumerate() {
+        let y = 80.0 + i as f32 * 16.0;
+        let color = cfg::SHIP_COLOR_BY_PLAYER[player.id];
+        text::draw_text(&format!("PLAYER {}", player.id + 1), 120.0, y, 12.0, color);
+        let rounds = format!("ROUNDS {}", player.rounds_won);
+        text::draw_text(&rounds, 210.0, y, 12.0, palette::LIGHTGRAY);
+        let kills = format!("KILLS {}", player.kills);
+        text::draw_text(&kills, 290.0, y, 12.0, palette::LIGHTGRAY);
+    }
+    if game.break_timer == 0.0 {
+        draw_centered_text("PAUSE FOR A REMATCH", cfg::ARENA_HEIGHT - 8.0, 12, palette::DARKGRAY);
+    }
+}
+
+/// The first player's score, lives and shield go in the top left corner, the second's in
+/// the top right one. A versus match shows the rounds won in place of the lives.
+fn draw_player_hud(player: &entity::Player, game: &asteroids::Game) {
+    const BAR_WIDTH: f32 = 30.0;
+    let is_right = player.id % 2 == 1;
+    // where a left aligned element of the given width starts
+    let left = |width: f32| {
+        if is_right {
+            cfg::ARENA_WIDTH - 4.0 - width
+        } else {
+            4.0
+        }
+    };
+    let score = player.score.to_string();
+    let score_width = text::measure_text(&score, None, 12, 1.0).width;
+    text::draw_text(&score, left(score_width), 12.0, 12.0, palette::LIGHTGRAY);
+    let color = cfg::SHIP_COLOR_BY_PLAYER[player.id];
+    if game.mode.versus {
+        let rounds_left = left(player.rounds_won as f32 * 8.0 - 2.0);
+        for i in 0..player.rounds_won {
+            let x = rounds_left + i as f32 * 8.0;
+            shapes::draw_rectangle(x, 16.0, 6.0, 6.0, color);
+        }
+    } else {
+        let lives_left = left(player.lives as f32 * 8.0 - 2.0);
+        for i in 0..player.lives {
+            let x = lives_left + 3.0 + i as f32 * 8.0;
+            shapes::draw_triangle(
+                math::vec2(x, 16.0),
+                math::vec2(x - 3.0, 22.0),
+                math::vec2(x + 3.0, 22.0),
+                color,
+            );
+        }
+    }
+    if let Some(ship) = &player.ship {
+        let energy_width = BAR_WIDTH * ship.shield_energy / game.config.shield.max_energy;
+        let x = left(BAR_WIDTH);
+        shapes::draw_rectangle(x, 26.0, BAR_WIDTH, 3.0, palette::DARKGRAY);
+        shapes::draw_rectangle(x, 26.0, energy_width, 3.0, cfg::SHIELD_COLOR);
+    }
+}
+
+fn draw_touch_buttons() {
+    for (center, action) in pointer::touch_buttons() {
+        let label = match action {
+            entity::Action::Accelerate => "THRUST",
+            _ => "FIRE",
+        };
+        let radius = pointer::TOUCH_BUTTON_RADIUS;
+        shapes::draw_circle_lines(center.x, center.y, radius, 1.0, palette::DARKGRAY);
+        let text_size = text::measure_text(label, None, 8, 1.0);
+        let x = center.x - text_size.width / 2.0;
+        text::draw_text(label, x, center.y + 3.0, 8.0, palette::DARKGRAY);
+    }
+}
+
+fn draw_controls(screen: &settings::ControlsScreen, keymap: &keymap::Keymap) {
+    let title = format!("PLAYER {} CONTROLS", screen.player_id + 1);
+    draw_centered_text(&title, 24.0, 16, palette::WHITE);
+    for (i, &action) in entity::Action::ALL.iter().enumerate() {
+        let y = 48.0 + i as f32 * 16.0;
+        let is_selected = i == screen.selected;
+        let color = if is_selected {
+            palette::WHITE
+        } else {
+            palette::LIGHTGRAY
+        };
+        let keys = if is_selected && screen.is_waiting_for_key {
+            "PRESS A KEY".to_string()
+        } else {
+            let key_names: Vec<_> = keymap
+                .keys(action)
+                .iter()
+                .map(|k| format!("{:?}", k))
+                .collect();
+            key_names.join(", ").to_uppercase()
+        };
+        let name = keymap::action_name(action).replace('_', " ").to_uppercase();
+        if is_selected {
+            text::draw_text(">", 96.0, y, 12.0, color);
+        }
+        text::draw_text(&name, 108.0, y, 12.0, color);
+        text::draw_text(&keys, 240.0, y, 12.0, color);
+    }
+    if let Some(error) = &screen.error {
+        draw_centered_text(&error.to_uppercase(), cfg::ARENA_HEIGHT - 24.0, 12, palette::RED);
+    }
+    draw_centered_text(
+        "ENTER ADD KEY  BACKSPACE REMOVE KEY  TAB PLAYER  ESC BACK",
+        cfg::ARENA_HEIGHT - 8.0,
+        12,
+        palette::DARKGRAY,
+    );
+}
+
+fn draw_centered_text(text: &str, y: f32, font_size: u16, color: color::Color) {
+    let text_size = text::measure_text(text, None, font_size, 1.0);
+    let x = (cfg::ARENA_WIDTH - text_size.width) / 2.0;
+    text::draw_text(text, x, y, font_size as f32, color);
+}
+
+/// Blends the last two simulation steps, going the short way across a wrapped edge.
+fn interpolate(prev_pos: math::Vec2, pos: math::Vec2, alpha: f32) -> math::Vec2 {
+    pos - arena::wrapped_delta(prev_pos, pos) * (1.0 - alpha)
+}
+
+fn draw_ship(smooth_pos: math::Vec2, sprite: &cmpt::Sprite, has_exhaust: bool, is_shielded: bool) {
+    let position = math::vec2(smooth_pos.x as i32 as f32 + 0.5, smooth_pos.y as i32 as f32 + 0.5);
+    let &cmpt::Sprite { ref variant, angle, .. } = sprite;
+    let radius = cfg::SHIP_DRAW_RADIUS;
+    let layers = match variant {
+        cmpt::SpriteVariant::Vector { layers } => layers,
+        _ => unreachable!(),
+    };
+    // let draw_points = vec![
+    //     math::vec2(1.0, 0.0) * radius,
+    //     math::vec2(-0.70710677, 0.70710677) * radius,
+    //     math::vec2(-0.31606203, 0.2451628) * radius,
+    //     math::vec2(-0.31606197, -0.24516287) * radius,
+    //     math::vec2(-0.70710665, -0.7071069) * radius,
+    // ];
+    // draw_polygon(&layers[0].0, position, 0.0, layers[0].1);
+    draw_layers(layers, position, angle);
+    if has_exhaust {
+        let v1_offset = math::vec2(
+            (angle + PI * 0.85).cos() * radius * 0.55,
+            (angle + PI * 0.85).sin() * radius * 0.55,
+        );
+        let v2_offset = math::vec2(
+            (angle + PI * rand::gen_range(0.98, 1.02)).cos() * radius * rand::gen_range(0.75, 1.25),
+            (angle + PI * rand::gen_range(0.98, 1.02)).sin() * radius * rand::gen_range(0.75, 1.25),
+        );
+        let v3_offset = math::vec2(
+            (angle + PI * 1.15).cos() * radius * 0.55,
+            (angle + PI * 1.15).sin() * radius * 0.55,
+        );
+        shapes::draw_triangle(
+            position + v1_offset,
+            position + v2_offset,
+            position + v3_offset,
+            palette::WHITE,
+        );
+    }
+    if is_shielded {
+        shapes::draw_circle_lines(
+            position.x,
+            position.y,
+            cfg::SHIELD_DRAW_RADIUS,
+            1.0,
+            cfg::SHIELD_COLOR,
+        );
+    }
+}
+
+fn draw_polygon(
+    draw_points: &[math::Vec2],
+    offset: math::Vec2,
+    rotation: f32,
+    color: color::Color,
+) {
+    use macroquad::prelude::{DrawMode, Vertex};
+    let gl = unsafe { window::get_internal_gl().quad_gl };
+    let vertices: Vec<_> = draw_points
+        .iter()
+        .map(|&p| math::Mat2::from_angle(rotation).mul_vec2(p))
+        .map(|p| p + offset)
+        .map(|p| Vertex::new(p.x, p.y, 0.0, 0.0, 0.0, color))
+        .collect();
+    let indices: Vec<_> = (1..(draw_points.len() as u16 - 1))
+        .flat_map(|i| [0, i, i + 1])
+        .collect();
+    gl.texture(None);
+    gl.draw_mode(DrawMode::Triangles);
+    gl.geometry(&vertices, &indices);
+}
+
+fn draw_layers(layers: &[(Vec<math::Vec2>, color::Color)], offset: math::Vec2, rotation: f32) {
+    for (draw_points, color) in layers {
+        draw_polygon(draw_points, offset, rotation, *color);
+    }
+}
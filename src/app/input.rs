@@ -0,0 +1,169 @@
+use crate::{cfg, entity, render};
+use macroquad::{
+    input::{self, KeyCode},
+    math,
+};
+
+pub fn update(app: &mut crate::App, _dt: f32) {
+    // a pressed action is kept until a simulation step consumes it
+    for input in &mut app.player_inputs {
+        input
+            .actions
+            .retain(|action| action.is_triggered_by_press());
+    }
+    let mut gamepad = app.gamepads.poll(&app.settings.gamepad);
+    let mut pointer = app
+        .pointer
+        .as_mut()
+        .map(|pointer| pointer.poll(&render::Letterbox::of_window()))
+        .unwrap_or_default();
+    let local_player = app
+        .netplay
+        .as_ref()
+        .map_or(0, |session| session.local_player);
+    let local_ship = app
+        .game
+        .players
+        .get(local_player)
+        .and_then(|p| p.ship.as_ref());
+    // held turn keys take over from the pointer, so the keys still work with it
+    let is_turn_key_down = app.settings.keymaps()[0].bindings().any(|(key, action)| {
+        matches!(action, entity::Action::TurnLeft | entity::Action::TurnRight)
+            && input::is_key_down(key)
+    });
+    let aim_heading = pointer
+        .aim
+        .filter(|_| !is_turn_key_down)
+        .zip(local_ship)
+        .and_then(|(aim, ship)| heading_towards(ship.position, aim));
+    app.player_inputs[0].steering = match aim_heading {
+        Some(heading) => Some(entity::Steering {
+            heading: Some(heading),
+            ..gamepad.steering.unwrap_or_default()
+        }),
+        None => gamepad.steering,
+    };
+    if app.controls_screen.is_some() {
+        update_controls_screen(app);
+        return;
+    }
+    let player_count = app.player_inputs.len();
+    let keymaps = &app.settings.keymaps()[..player_count];
+    match app.game.state {
+        entity::GameState::Pause | entity::GameState::MatchOver => {
+            use entity::Action::*;
+            // any player may pause or ask for a rematch, the first one's input carries it
+            if keymaps
+                .iter()
+                .flat_map(|keymap| keymap.keys(TogglePause))
+                .any(|&key| input::is_key_pressed(key))
+                || gamepad.pressed.contains(&TogglePause)
+            {
+                app.player_inputs[0].actions.insert(TogglePause);
+            } else if matches!(app.game.state, entity::GameState::Pause)
+                && input::is_key_pressed(KeyCode::F1)
+            {
+                app.controls_screen = Some(Default::default());
+            }
+        }
+        entity::GameState::LevelRunning => {
+            for (player_id, (keymap, player_input)) in
+                keymaps.iter().zip(&mut app.player_inputs).enumerate()
+            {
+                let mut pressed: Vec<_> = keymap
+                    .bindings()
+                    .filter(|&(key, _)| input::is_key_pressed(key))
+                    .map(|(_, action)| action)
+                    .collect();
+                let mut held: Vec<_> = keymap
+                    .bindings()
+                    .filter(|&(key, _)| input::is_key_down(key))
+                    .map(|(_, action)| action)
+                    .collect();
+                if player_id == 0 {
+                    pressed.append(&mut gamepad.pressed);
+                    held.append(&mut gamepad.held);
+                    held.append(&mut pointer.held);
+                }
+                for action in pressed {
+                    if action == entity::Action::ToggleDebugInfo {
+                        app.renderer.show_debug_info = !app.renderer.show_debug_info;
+                    } else if action.is_triggered_by_press() {
+                        player_input.actions.insert(action);
+                    }
+                }
+                for action in held {
+                    if !action.is_triggered_by_press() {
+                        player_input.actions.insert(action);
+                    }
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+/// `None` with the aim on the ship, where the direction flips with every tiny move.
+fn heading_towards(position: math::Vec2, aim: math::Vec2) -> Option<f32> {
+    let delta = aim - position;
+    (delta.length() > cfg::SHIP_DRAW_RADIUS).then(|| delta.y.atan2(delta.x))
+}
+
+/// The screen's own keys are fixed, so a broken keymap can always be fixed from it.
+fn update_controls_screen(app: &mut crate::App) {
+    let screen = app.controls_screen.as_mut().unwrap();
+    let action = entity::Action::ALL[screen.selected];
+    if screen.is_waiting_for_key {
+        // any key is taken here, the screen's own ones too
+        if let Some(key) = input::get_last_key_pressed() {
+            screen.is_waiting_for_key = false;
+            screen.error = app.settings.bind(screen.player_id, action, key).err();
+        }
+        return;
+    }
+    let action_count = entity::Action::ALL.len();
+    if input::is_key_pressed(KeyCode::Up) {
+        screen.selected = (screen.selected + action_count - 1) % action_count;
+    } else if input::is_key_pressed(KeyCode::Down) {
+        screen.selected = (screen.selected + 1) % action_count;
+    } else if input::is_key_pressed(KeyCode::Enter) {
+        screen.is_waiting_for_key = true;
+        screen.error = None;
+    } else if input::is_key_pressed(KeyCode::Backspace) {
+        let keymap = app.settings.keymap_mut(screen.player_id);
+        screen.error = keymap.unbind_last(action).err();
+    } else if input::is_key_pressed(KeyCode::Tab) {
+        screen.player_id = 1 - screen.player_id;
+        screen.error = None;
+    } else if input::is_key_pressed(KeyCode::Escape) {
+        app.controls_screen = None;
+        if let Some(path) = &app.settings_path {
+            if let Err(err) = app.settings.save(path) {
+                eprintln!("{}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn heading_points_from_the_ship_to_the_aim() {
+        let position = math::vec2(100.0, 100.0);
+        let heading = heading_towards(position, math::vec2(100.0, 150.0)).unwrap();
+        assert!((heading - PI / 2.0).abs() < 1e-6);
+        let heading = heading_towards(position, math::vec2(50.0, 100.0)).unwrap();
+        assert!((heading.abs() - PI).abs() < 1e-6);
+    }
+
+    #[test]
+    fn aim_on_the_ship_gives_no_heading() {
+        let position = math::vec2(100.0, 100.0);
+        assert_eq!(heading_towards(position, position), None);
+        let aim = position + math::vec2(cfg::SHIP_DRAW_RADIUS * 0.5, 0.0);
+        assert_eq!(heading_towards(position, aim), None);
+    }
+}
@@ -0,0 +1,101 @@
+//! Geometry of the arena, whose edges wrap around like a torus.
+
+use crate::cfg;
+use macroquad::math;
+
+/// The shortest vector from `from` to `to`, possibly going across the edges.
+pub fn wrapped_delta(from: math::Vec2, to: math::Vec2) -> math::Vec2 {
+    let d_pos = to - from;
+    math::vec2(
+        (d_pos.x + cfg::ARENA_WIDTH / 2.0).rem_euclid(cfg::ARENA_WIDTH) - cfg::ARENA_WIDTH / 2.0,
+        (d_pos.y + cfg::ARENA_HEIGHT / 2.0).rem_euclid(cfg::ARENA_HEIGHT) - cfg::ARENA_HEIGHT / 2.0,
+    )
+}
+
+/// Uniform grid over the arena for finding items near a point, wrapping at the edges.
+///
+/// Cells are at least `cell_size` wide, so items closer than that to a point are always
+/// in its cell or in one of the eight around it.
+pub struct Grid {
+    cols: usize,
+    rows: usize,
+    cells: Vec<Vec<usize>>,
+}
+
+impl Grid {
+    pub fn new(cell_size: f32) -> Self {
+        let cols = usize::max(1, (cfg::ARENA_WIDTH / cell_size) as usize);
+        let rows = usize::max(1, (cfg::ARENA_HEIGHT / cell_size) as usize);
+        Grid {
+            cols,
+            rows,
+            cells: vec![Vec::new(); cols * rows],
+        }
+    }
+
+    pub fn insert(&mut self, item: usize, position: math::Vec2) {
+        let (col, row) = self.cell_of(position);
+        self.cells[row * self.cols + col].push(item);
+    }
+
+    /// Items in the cell of `position` and in the cells around it.
+    pub fn near(&self, position: math::Vec2) -> impl Iterator<Item = usize> + '_ {
+        let (col, row) = self.cell_of(position);
+        neighbours(row, self.rows)
+            .flat_map(move |row| neighbours(col, self.cols).map(move |col| (col, row)))
+            .flat_map(move |(col, row)| self.cells[row * self.cols + col].iter().copied())
+    }
+
+    fn cell_of(&self, position: math::Vec2) -> (usize, usize) {
+        let col = position.x.rem_euclid(cfg::ARENA_WIDTH) / cfg::ARENA_WIDTH * self.cols as f32;
+        let row = position.y.rem_euclid(cfg::ARENA_HEIGHT) / cfg::ARENA_HEIGHT * self.rows as f32;
+        (
+            usize::min(col as usize, self.cols - 1),
+            usize::min(row as usize, self.rows - 1),
+        )
+    }
+}
+
+/// Indices of a cell and its neighbours along one axis, each listed once.
+fn neighbours(index: usize, count: usize) -> impl Iterator<Item = usize> {
+    // an axis of one or two cells has no separate neighbours on both sides
+    let first = if count >= 3 { index + count - 1 } else { 0 };
+    (0..usize::min(count, 3)).map(move |i| (first + i) % count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    #[test]
+    fn grid_finds_everything_within_cell_size() {
+        let mut rng = Rng::new(1);
+        let mut random_position = || {
+            math::vec2(
+                rng.gen_range(-5.0, cfg::ARENA_WIDTH + 5.0),
+                rng.gen_range(0.0, cfg::ARENA_HEIGHT),
+            )
+        };
+        for cell_size in [32.0, 150.0, 500.0] {
+            let items: Vec<_> = (0..300).map(|_| random_position()).collect();
+            let mut grid = Grid::new(cell_size);
+            for (i, &position) in items.iter().enumerate() {
+                grid.insert(i, position);
+            }
+            for _ in 0..300 {
+                let position = random_position();
+                let near: Vec<_> = grid.near(position).collect();
+                let mut unique = near.clone();
+                unique.sort_unstable();
+                unique.dedup();
+                assert_eq!(near.len(), unique.len());
+                for (i, &item) in items.iter().enumerate() {
+                    if wrapped_delta(position, item).length() <= cell_size {
+                        assert!(near.contains(&i));
+                    }
+                }
+            }
+        }
+    }
+}
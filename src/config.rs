@@ -0,0 +1,372 @@
+//! Tuning values a designer may want to change without recompiling.
+//!
+//! A config file is TOML with one table per section, named like the fields of [`Config`].
+//! Keys left out keep their default, so a file only needs the values being tuned:
+//!
+//! ```toml
+//! [ship]
+//! accel = 240.0
+//! turn_speed = 5.0
+//! ```
+
+use crate::entity::cmpt::{AsteroidSplit, AsteroidStage};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub ship: ShipConfig,
+    pub hyperspace: HyperspaceConfig,
+    pub shield: ShieldConfig,
+    pub bullets: BulletConfig,
+    pub aliens: AlienConfig,
+    pub asteroids: AsteroidConfig,
+    pub waves: WaveConfig,
+    pub versus: VersusConfig,
+    pub collisions: CollisionConfig,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShipConfig {
+    pub max_speed: f32,
+    pub accel: f32,
+    pub decel: f32,
+    pub turn_speed: f32,
+    pub hit_radius: f32,
+    pub lives: u32,
+    pub safe_radius: f32,
+    pub respawn_delay: f32,
+    pub invulnerability_time: f32,
+    pub extra_life_score: u32,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HyperspaceConfig {
+    pub time: f32,
+    pub failure_chance: f32,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShieldConfig {
+    pub max_energy: f32,
+    pub min_energy: f32,
+    pub drain_speed: f32,
+    pub recharge_speed: f32,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BulletConfig {
+    pub cooldown: f32,
+    pub ship_speed: f32,
+    pub ship_timer_limit: f32,
+    pub alien_speed: f32,
+    pub alien_timer_limit_by_kind: [f32; 2],
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlienConfig {
+    pub hit_radius_by_kind: [f32; 2],
+    pub score_by_kind: [u32; 2],
+    pub spawn_period: f32,
+    pub min_spawn_period: f32,
+    pub shoot_period: f32,
+    pub shift_period: f32,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AsteroidConfig {
+    /// Fastest turn of the smallest asteroids in radians per second, bigger ones turn slower.
+    pub max_spin: f32,
+    pub split: AsteroidSplit,
+    /// From the smallest to the one every wave starts with.
+    pub stages: Vec<AsteroidStage>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WaveConfig {
+    pub start_asteroids: usize,
+    pub max_asteroids: usize,
+    pub speed_step: f32,
+    pub alien_period_factor: f32,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VersusConfig {
+    /// Kills that win a round.
+    pub target_score: u32,
+    /// Rounds that win the match.
+    pub rounds_to_win: u32,
+    pub asteroids: usize,
+    pub round_start_time: f32,
+    pub round_over_time: f32,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollisionConfig {
+    /// Bodies collide by their drawn outlines rather than their hit circles.
+    pub polygons: bool,
+}
+
+impl Default for ShipConfig {
+    fn default() -> Self {
+        ShipConfig {
+            max_speed: 160.0,
+            accel: 200.0,
+            decel: 0.08,
+            turn_speed: 6.0,
+            hit_radius: 4.0,
+            lives: 3,
+            safe_radius: crate::cfg::ARENA_HEIGHT * 0.3,
+            respawn_delay: 2.0,
+            invulnerability_time: 3.0,
+            extra_life_score: 10000,
+        }
+    }
+}
+
+impl Default for HyperspaceConfig {
+    fn default() -> Self {
+        HyperspaceConfig { time: 0.6, failure_chance: 0.1 }
+    }
+}
+
+impl Default for ShieldConfig {
+    fn default() -> Self {
+        ShieldConfig {
+            max_energy: 1.0,
+            min_energy: 0.2,
+            drain_speed: 0.5,
+            recharge_speed: 0.1,
+        }
+    }
+}
+
+impl Default for BulletConfig {
+    fn default() -> Self {
+        BulletConfig {
+            cooldown: 0.3,
+            ship_speed: 240.0,
+            ship_timer_limit: 0.8,
+            alien_speed: 100.0,
+            alien_timer_limit_by_kind: [0.9, 1.3],
+        }
+    }
+}
+
+impl Default for AlienConfig {
+    fn default() -> Self {
+        AlienConfig {
+            hit_radius_by_kind: [5.5, 4.4],
+            score_by_kind: [200, 1000],
+            spawn_period: 30.0,
+            min_spawn_period: 10.0,
+            shoot_period: 1.3,
+            shift_period: 1.0,
+        }
+    }
+}
+
+impl Default for AsteroidConfig {
+    #[rustfmt::skip]
+    fn default() -> Self {
+        AsteroidConfig {
+            max_spin: 2.4,
+            split: Default::default(),
+            stages: vec![
+                AsteroidStage { max_speed: 72.0, radius: 4.8, score: 100 },
+                AsteroidStage { max_speed: 48.0, radius: 11.2, score: 50 },
+                AsteroidStage { max_speed: 24.0, radius: 16.0, score: 20 },
+            ],
+        }
+    }
+}
+
+impl Default for AsteroidSplit {
+    fn default() -> Self {
+        AsteroidSplit {
+            inherited_velocity: 1.0,
+            spread: 0.6,
+            impact_speed: 10.0,
+        }
+    }
+}
+
+impl Default for WaveConfig {
+    fn default() -> Self {
+        WaveConfig {
+            start_asteroids: 5,
+            max_asteroids: 11,
+            speed_step: 0.1,
+            alien_period_factor: 0.85,
+        }
+    }
+}
+
+impl Default for VersusConfig {
+    fn default() -> Self {
+        VersusConfig {
+            target_score: 5,
+            rounds_to_win: 2,
+            asteroids: 4,
+            round_start_time: 2.0,
+            round_over_time: 3.0,
+        }
+    }
+}
+
+impl Default for CollisionConfig {
+    fn default() -> Self {
+        CollisionConfig { polygons: true }
+    }
+}
+
+impl Config {
+    /// Parses and validates a config file, the error names the offending key.
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let config: Config = toml::from_str(text).map_err(|err| err.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("config is always representable in TOML")
+    }
+
+    /// Catches values the game can't run with; NaN fails every check.
+    pub fn validate(&self) -> Result<(), String> {
+        let Config {
+            ship,
+            hyperspace,
+            shield,
+            bullets,
+            aliens,
+            asteroids,
+            waves,
+            versus,
+            collisions: _,
+        } = self;
+        positive("ship.max_speed", ship.max_speed)?;
+        positive("ship.accel", ship.accel)?;
+        non_negative("ship.decel", ship.decel)?;
+        positive("ship.turn_speed", ship.turn_speed)?;
+        positive("ship.hit_radius", ship.hit_radius)?;
+        check("ship.lives", ship.lives > 0, "must be at least 1")?;
+        // two versus ships leave no spot farther away than this on the wrapping arena
+        let max_safe_radius =
+            f32::hypot(crate::cfg::ARENA_WIDTH / 4.0, crate::cfg::ARENA_HEIGHT / 2.0);
+        check(
+            "ship.safe_radius",
+            (0.0..max_safe_radius).contains(&ship.safe_radius),
+            &format!("must be from 0 to below {}, got {}", max_safe_radius, ship.safe_radius),
+        )?;
+        non_negative("ship.respawn_delay", ship.respawn_delay)?;
+        non_negative("ship.invulnerability_time", ship.invulnerability_time)?;
+        check("ship.extra_life_score", ship.extra_life_score > 0, "must be at least 1")?;
+        non_negative("hyperspace.time", hyperspace.time)?;
+        in_range("hyperspace.failure_chance", hyperspace.failure_chance, 0.0, 1.0)?;
+        positive("shield.max_energy", shield.max_energy)?;
+        in_range("shield.min_energy", shield.min_energy, 0.0, shield.max_energy)?;
+        non_negative("shield.drain_speed", shield.drain_speed)?;
+        non_negative("shield.recharge_speed", shield.recharge_speed)?;
+        non_negative("bullets.cooldown", bullets.cooldown)?;
+        positive("bullets.ship_speed", bullets.ship_speed)?;
+        positive("bullets.ship_timer_limit", bullets.ship_timer_limit)?;
+        positive("bullets.alien_speed", bullets.alien_speed)?;
+        for (i, &value) in bullets.alien_timer_limit_by_kind.iter().enumerate() {
+            positive(&format!("bullets.alien_timer_limit_by_kind[{}]", i), value)?;
+        }
+        for (i, &value) in aliens.hit_radius_by_kind.iter().enumerate() {
+            positive(&format!("aliens.hit_radius_by_kind[{}]", i), value)?;
+        }
+        positive("aliens.spawn_period", aliens.spawn_period)?;
+        positive("aliens.min_spawn_period", aliens.min_spawn_period)?;
+        positive("aliens.shoot_period", aliens.shoot_period)?;
+        positive("aliens.shift_period", aliens.shift_period)?;
+        non_negative("asteroids.max_spin", asteroids.max_spin)?;
+        non_negative("asteroids.split.inherited_velocity", asteroids.split.inherited_velocity)?;
+        non_negative("asteroids.split.spread", asteroids.split.spread)?;
+        non_negative("asteroids.split.impact_speed", asteroids.split.impact_speed)?;
+        check("asteroids.stages", !asteroids.stages.is_empty(), "must not be empty")?;
+        for (i, stage) in asteroids.stages.iter().enumerate() {
+            positive(&format!("asteroids.stages[{}].max_speed", i), stage.max_speed)?;
+            positive(&format!("asteroids.stages[{}].radius", i), stage.radius)?;
+        }
+        check("waves.start_asteroids", waves.start_asteroids > 0, "must be at least 1")?;
+        check("waves.max_asteroids", waves.max_asteroids > 0, "must be at least 1")?;
+        non_negative("waves.speed_step", waves.speed_step)?;
+        positive("waves.alien_period_factor", waves.alien_period_factor)?;
+        check("versus.target_score", versus.target_score > 0, "must be at least 1")?;
+        check("versus.rounds_to_win", versus.rounds_to_win > 0, "must be at least 1")?;
+        non_negative("versus.round_start_time", versus.round_start_time)?;
+        non_negative("versus.round_over_time", versus.round_over_time)?;
+        Ok(())
+    }
+}
+
+fn check(key: &str, is_valid: bool, requirement: &str) -> Result<(), String> {
+    if is_valid {
+        Ok(())
+    } else {
+        Err(format!("invalid value for key `{}`: {}", key, requirement))
+    }
+}
+
+fn positive(key: &str, value: f32) -> Result<(), String> {
+    check(key, value > 0.0, &format!("must be positive, got {}", value))
+}
+
+fn non_negative(key: &str, value: f32) -> Result<(), String> {
+    check(key, value >= 0.0, &format!("must not be negative, got {}", value))
+}
+
+fn in_range(key: &str, value: f32, min: f32, max: f32) -> Result<(), String> {
+    check(
+        key,
+        (min..=max).contains(&value),
+        &format!("must be from {} to {}, got {}", min, max, value),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_survive_toml_roundtrip() {
+        let config = Config::default();
+        assert_eq!(Config::from_toml(&config.to_toml()), Ok(config));
+    }
+
+    #[test]
+    fn missing_keys_keep_defaults() {
+        let config = Config::from_toml("[ship]\naccel = 250.0\n").unwrap();
+        assert_eq!(config.ship.accel, 250.0);
+        assert_eq!(config.ship.decel, ShipConfig::default().decel);
+        assert_eq!(config.asteroids, AsteroidConfig::default());
+    }
+
+    #[test]
+    fn errors_name_the_key() {
+        let err = Config::from_toml("[ship]\nacel = 250.0\n").unwrap_err();
+        assert!(err.contains("acel"), "{}", err);
+        let err = Config::from_toml("[shield]\ndrain_speed = \"fast\"\n").unwrap_err();
+        assert!(err.contains("drain_speed"), "{}", err);
+        let err =
+            Config::from_toml("[[asteroids.stages]]\nmax_speed = 10.0\nradius = -1.0\nscore = 5\n")
+                .unwrap_err();
+        assert!(err.contains("asteroids.stages[0].radius"), "{}", err);
+        let err = Config::from_toml("[waves]\nstart_asteroids = 0\n").unwrap_err();
+        assert!(err.contains("waves.start_asteroids"), "{}", err);
+        let err = Config::from_toml("[ship]\nsafe_radius = 250.0\n").unwrap_err();
+        assert!(err.contains("ship.safe_radius"), "{}", err);
+    }
+}
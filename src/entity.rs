@@ -1,19 +1,37 @@
-use crate::{cfg, palette, sprites};
+use crate::{cfg, config::Config, palette, rng::Rng, sprites};
 use cmpt::*;
-use macroquad::{color, material, math, rand, texture};
-use macroquad_particles as particles;
+use macroquad::{color, math};
+use std::collections::HashSet;
 use std::f32::consts::PI;
 
 /// Components for game entities.
 pub mod cmpt {
+    use crate::cfg;
     use macroquad::{color, math};
+    use serde::{Deserialize, Serialize};
 
+    #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+    #[serde(deny_unknown_fields)]
     pub struct AsteroidStage {
         pub max_speed: f32,
         pub radius: f32,
+        pub score: u32,
     }
 
-    #[derive(PartialEq)]
+    /// How the two children of a destroyed asteroid move away from it.
+    #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct AsteroidSplit {
+        /// Share of the parent's velocity each child keeps.
+        pub inherited_velocity: f32,
+        /// Speed the children fly apart at, across the impact direction, as a share of
+        /// their stage's `max_speed`. The children go opposite ways, so it adds no momentum.
+        pub spread: f32,
+        /// Speed both children get along the direction of the bullet that hit the parent.
+        pub impact_speed: f32,
+    }
+
+    #[derive(Clone, PartialEq)]
     pub enum SpriteVariant {
         Bullet {
             color: color::Color,
@@ -23,16 +41,42 @@ pub mod cmpt {
         },
     }
 
+    #[derive(Clone)]
     pub struct Sprite {
         pub variant: SpriteVariant,
         pub angle: f32,
     }
-    #[derive(Default)]
+
+    impl Sprite {
+        /// Distance from the sprite origin to its farthest drawn point.
+        pub fn reach(&self) -> f32 {
+            match &self.variant {
+                SpriteVariant::Bullet { .. } => cfg::BULLET_LENGTH,
+                SpriteVariant::Vector { layers } => layers
+                    .iter()
+                    .flat_map(|(points, _)| points)
+                    .fold(0.0, |reach, point| f32::max(reach, point.length())),
+            }
+        }
+    }
+    #[derive(Clone, Default)]
     pub struct Body {
         pub radius: f32,
-        pub angle: f32,
-        pub speed: f32,
+        pub velocity: math::Vec2,
+        /// Turn rate of the sprite, in radians per second.
+        pub angular_velocity: f32,
         pub is_hit: bool,
+        /// The player whose bullet hit the body, who scores for it.
+        pub hit_by_player: Option<usize>,
+        /// Direction of the last bullet that hit the body.
+        pub impact_angle: Option<f32>,
+    }
+
+    impl Body {
+        /// Everything is made of the same stuff, so mass goes with the area.
+        pub fn mass(&self) -> f32 {
+            self.radius.powi(2)
+        }
     }
 
     #[derive(Copy, Clone)]
@@ -41,95 +85,164 @@ pub mod cmpt {
         ToLeft,
     }
 
-    #[derive(Copy, Clone)]
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
     pub enum AlienKind {
         Big,
         Small,
     }
+
+    #[derive(Copy, Clone)]
+    pub enum ExplosionKind {
+        Ship,
+        Alien,
+        Asteroid,
+    }
 }
 
+/// Who fired a bullet.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Owner {
+    /// The id of the player whose ship fired.
+    Player(usize),
+    Alien(AlienKind),
+}
+
+#[derive(Clone)]
 pub struct Bullet {
     pub position: math::Vec2,
+    pub prev_position: math::Vec2,
     pub sprite: Sprite,
     pub body: Body,
     pub life_timer: f32,
-    pub from_enemy: bool,
+    pub owner: Owner,
 }
 
 impl Bullet {
-    pub fn new(position: math::Vec2, angle: f32, alien_kind: Option<AlienKind>) -> Self {
-        let (color, speed, life_timer) = if let Some(kind) = alien_kind {
-            (
+    pub fn new(position: math::Vec2, angle: f32, owner: Owner, config: &Config) -> Self {
+        let (color, speed, life_timer) = match owner {
+            Owner::Player(id) => (
+                cfg::SHIP_BULLET_COLOR_BY_PLAYER[id],
+                config.bullets.ship_speed,
+                config.bullets.ship_timer_limit,
+            ),
+            Owner::Alien(kind) => (
                 cfg::ALIEN_BULLET_COLOR,
-                cfg::ALIEN_BULLET_SPEED,
-                cfg::ALIEN_BULLET_TIMER_LIMIT_BY_KIND[kind as usize],
-            )
-        } else {
-            (
-                cfg::SHIP_BULLET_COLOR,
-                cfg::SHIP_BULLET_SPEED,
-                cfg::SHIP_BULLET_TIMER_LIMIT,
-            )
+                config.bullets.alien_speed,
+                config.bullets.alien_timer_limit_by_kind[kind as usize],
+            ),
         };
         Bullet {
             position,
+            prev_position: position,
             sprite: Sprite {
                 variant: SpriteVariant::Bullet { color },
                 angle,
             },
             body: Body {
                 radius: cfg::BULLET_RADIUS,
-                angle,
-                speed,
-                is_hit: false,
+                velocity: math::vec2(angle.cos(), angle.sin()) * speed,
+                ..Default::default()
             },
             life_timer,
-            from_enemy: alien_kind.is_some(),
+            owner,
         }
     }
 }
 
+#[derive(Clone)]
 pub struct Ship {
     pub position: math::Vec2,
+    pub prev_position: math::Vec2,
     pub sprite: Sprite,
     pub body: Body,
     pub has_exhaust: bool,
     pub is_destroyed: bool,
     pub weapon_cooldown_timer: f32,
+    pub invulnerability_timer: f32,
+    pub is_in_hyperspace: bool,
+    pub hyperspace_timer: f32,
+    pub is_shielded: bool,
+    pub shield_energy: f32,
 }
 
 impl Ship {
-    pub fn new() -> Self {
+    pub fn new(player_id: usize, config: &Config) -> Self {
+        let mut layers = create_layers(sprites::SHIP, cfg::SHIP_DRAW_RADIUS);
+        // the sprite is drawn in the first player's colors
+        for (_, color) in &mut layers {
+            if *color == cfg::SHIP_COLOR_BY_PLAYER[0] {
+                *color = cfg::SHIP_COLOR_BY_PLAYER[player_id];
+            }
+        }
         Ship {
             position: math::vec2(cfg::ARENA_WIDTH / 2.0, cfg::ARENA_HEIGHT / 2.0),
+            prev_position: math::vec2(cfg::ARENA_WIDTH / 2.0, cfg::ARENA_HEIGHT / 2.0),
             sprite: Sprite {
-                variant: SpriteVariant::Vector {
-                    layers: create_layers(sprites::SHIP, cfg::SHIP_DRAW_RADIUS),
-                },
+                variant: SpriteVariant::Vector { layers },
                 angle: 0.0,
             },
             body: Body {
-                radius: cfg::SHIP_HIT_RADIUS,
-                angle: 0.0,
-                speed: 0.0,
-                is_hit: false,
+                radius: config.ship.hit_radius,
+                ..Default::default()
             },
             has_exhaust: false,
             is_destroyed: false,
             weapon_cooldown_timer: 0.0,
+            invulnerability_timer: 0.0,
+            is_in_hyperspace: false,
+            hyperspace_timer: 0.0,
+            is_shielded: false,
+            shield_energy: config.shield.max_energy,
         }
     }
 }
 
+#[derive(Clone)]
+pub struct Player {
+    /// Index into [`crate::Game::players`], also in the [`Owner`] of the player's bullets.
+    pub id: usize,
+    pub ship: Option<Ship>,
+    pub actions: HashSet<Action>,
+    pub steering: Option<Steering>,
+    /// Points in the waves, kills in the current round of a versus match.
+    pub score: u32,
+    pub lives: u32,
+    pub respawn_timer: f32,
+    /// Kills over the whole versus match.
+    pub kills: u32,
+    pub rounds_won: u32,
+}
+
+impl Player {
+    pub fn new(id: usize, config: &Config) -> Self {
+        Player {
+            id,
+            ship: None,
+            actions: HashSet::new(),
+            steering: None,
+            score: 0,
+            lives: config.ship.lives,
+            respawn_timer: 0.0,
+            kills: 0,
+            rounds_won: 0,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Explosion {
+    pub id: u32,
     pub position: math::Vec2,
+    pub prev_position: math::Vec2,
     pub body: Body,
-    pub emitter: particles::Emitter,
+    pub kind: ExplosionKind,
     pub life_timer: f32,
 }
 
+#[derive(Clone)]
 pub struct Alien {
     pub position: math::Vec2,
+    pub prev_position: math::Vec2,
     pub sprite: Sprite,
     pub body: Body,
     pub is_destroyed: bool,
@@ -140,18 +253,19 @@ pub struct Alien {
 }
 
 impl Alien {
-    pub fn new() -> Self {
-        let y = cfg::ARENA_HEIGHT * rand::gen_range(0.15, 0.85);
-        let direction = [AlienDirection::ToRight, AlienDirection::ToLeft][rand::gen_range(0, 2)];
+    pub fn new(rng: &mut Rng, config: &Config) -> Self {
+        let y = cfg::ARENA_HEIGHT * rng.gen_range(0.15, 0.85);
+        let direction = [AlienDirection::ToRight, AlienDirection::ToLeft][rng.gen_range(0, 2)];
         let x = cfg::ARENA_WIDTH * direction as u32 as f32;
         let angle = PI * direction as u32 as f32;
-        let kind = if rand::gen_range(0_u32, 10) < 3 {
+        let kind = if rng.gen_range(0_u32, 10) < 3 {
             AlienKind::Small
         } else {
             AlienKind::Big
         };
         Alien {
             position: math::vec2(x, y),
+            prev_position: math::vec2(x, y),
             sprite: Sprite {
                 variant: SpriteVariant::Vector {
                     layers: create_layers(
@@ -162,22 +276,23 @@ impl Alien {
                 angle: 0.0,
             },
             body: Body {
-                radius: cfg::ALIEN_HIT_RADIUS_BY_KIND[kind as usize],
-                angle,
-                speed: rand::gen_range(32.0, 40.0),
-                is_hit: false,
+                radius: config.aliens.hit_radius_by_kind[kind as usize],
+                velocity: math::vec2(angle.cos(), angle.sin()) * rng.gen_range(32.0, 40.0),
+                ..Default::default()
             },
             is_destroyed: false,
             kind,
             direction,
-            weapon_cooldown_timer: cfg::ALIEN_SHOOT_PERIOD,
+            weapon_cooldown_timer: config.aliens.shoot_period,
             shift_timer: 0.0,
         }
     }
 }
 
+#[derive(Clone)]
 pub struct Asteroid {
     pub position: math::Vec2,
+    pub prev_position: math::Vec2,
     pub sprite: Sprite,
     pub body: Body,
     pub is_destroyed: bool,
@@ -185,18 +300,18 @@ pub struct Asteroid {
 }
 
 impl Asteroid {
-    pub fn new(position: math::Vec2, stage: usize) -> Self {
-        let radius = cfg::ASTEROID_STAGES[stage].radius;
-        let max_speed = cfg::ASTEROID_STAGES[stage].max_speed;
+    pub fn new(position: math::Vec2, stage: usize, rng: &mut Rng, config: &Config) -> Self {
+        let radius = config.asteroids.stages[stage].radius;
+        let max_speed = config.asteroids.stages[stage].max_speed;
         let mut layers = Vec::new();
         layers.push({
             let mut draw_points = Vec::new();
             let mut draw_angle: f32 = 0.0;
             while draw_angle < PI * 2.0 {
-                let distance = rand::gen_range(0.95, 1.1) * radius;
+                let distance = rng.gen_range(0.95, 1.1) * radius;
                 draw_points
                     .push(math::vec2(draw_angle.cos() * distance, draw_angle.sin() * distance));
-                draw_angle += rand::gen_range(0.6, 1.2);
+                draw_angle += rng.gen_range(0.6, 1.2);
             }
             (draw_points, palette::DARKPURPLE)
         });
@@ -204,99 +319,36 @@ impl Asteroid {
             let mut draw_points = Vec::new();
             let mut draw_angle: f32 = 0.0;
             while draw_angle < PI * 2.0 {
-                let distance = rand::gen_range(0.5, 0.85) * radius;
+                let distance = rng.gen_range(0.5, 0.85) * radius;
                 draw_points
                     .push(math::vec2(draw_angle.cos() * distance, draw_angle.sin() * distance));
-                draw_angle += rand::gen_range(0.5, 0.7);
+                draw_angle += rng.gen_range(0.5, 0.7);
             }
             (draw_points, palette::LIGHTGRAY)
         });
-        let angle = rand::gen_range(0.0, 2.0 * PI);
-        let speed = max_speed * rand::gen_range(0.5, 1.0);
+        let angle = rng.gen_range(0.0, 2.0 * PI);
+        let speed = max_speed * rng.gen_range(0.5, 1.0);
+        let max_spin = config.asteroids.max_spin / (stage + 1) as f32;
+        let angular_velocity = rng.gen_range(-max_spin, max_spin);
         Asteroid {
             position,
+            prev_position: position,
             sprite: Sprite {
                 variant: SpriteVariant::Vector { layers },
                 angle: 0.0,
             },
-            body: Body { radius, angle, speed, is_hit: false },
+            body: Body {
+                radius,
+                velocity: math::vec2(angle.cos(), angle.sin()) * speed,
+                angular_velocity,
+                ..Default::default()
+            },
             is_destroyed: false,
             stage,
         }
     }
 }
 
-pub struct StarBackground {
-    pub static_emitter: particles::Emitter,
-    pub side_emitter: particles::Emitter,
-    pub side_emitter_pos: math::Vec2,
-}
-
-impl Default for StarBackground {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl StarBackground {
-    pub fn new() -> Self {
-        let static_cfg = stars();
-        let mut side_cfg = stars();
-        let side_emitter_pos;
-        side_cfg.lifetime_randomness = 0.0;
-        side_cfg.explosiveness = 0.0;
-        side_cfg.size_curve = None;
-        side_cfg.amount = 100;
-        // from left, from right, from top:
-        match rand::gen_range::<i32>(0, 3) {
-            multiplier @ (0 | 1) => {
-                side_cfg.initial_direction =
-                    math::vec2(1.0 - 2.0 * multiplier as f32, rand::gen_range(-0.3, 0.3));
-                side_cfg.emission_shape = particles::EmissionShape::Rect {
-                    width: 0.0,
-                    height: cfg::ARENA_HEIGHT * 1.2,
-                };
-                side_emitter_pos =
-                    math::vec2(cfg::ARENA_WIDTH * multiplier as f32, cfg::ARENA_HEIGHT / 2.0);
-            }
-            2 => {
-                side_cfg.initial_direction = math::vec2(rand::gen_range(-0.3, 0.3), 1.0);
-                side_cfg.emission_shape = particles::EmissionShape::Rect {
-                    width: cfg::ARENA_WIDTH * 1.2,
-                    height: 0.0,
-                };
-                side_emitter_pos = math::vec2(cfg::ARENA_WIDTH / 2.0, 0.0);
-            }
-            _ => unreachable!(),
-        }
-        StarBackground {
-            static_emitter: particles::Emitter::new(static_cfg),
-            side_emitter: particles::Emitter::new(side_cfg),
-            side_emitter_pos,
-        }
-    }
-}
-
-pub struct Renderer {
-    pub show_debug_info: bool,
-    pub canvas: macroquad_canvas::Canvas2D,
-    pub crt_effect: Option<material::Material>,
-}
-
-impl Default for Renderer {
-    fn default() -> Self {
-        let mut canvas = macroquad_canvas::Canvas2D::new(cfg::ARENA_WIDTH, cfg::ARENA_HEIGHT);
-        canvas
-            .get_texture_mut()
-            .set_filter(texture::FilterMode::Nearest);
-        Renderer {
-            show_debug_info: false,
-            canvas,
-            crt_effect: None,
-        }
-    }
-}
-
 fn create_layers(
     layers_ref: &[(&[math::Vec2], color::Color)],
     size: f32,
@@ -307,52 +359,50 @@ fn create_layers(
         .collect()
 }
 
-fn stars() -> particles::EmitterConfig {
-    particles::EmitterConfig {
-        lifetime: 70.0,
-        lifetime_randomness: 0.4,
-        amount: 50,
-        explosiveness: 0.2,
-        local_coords: true,
-        initial_direction: math::vec2(0.001, 0.0),
-        initial_velocity: 28.0,
-        initial_velocity_randomness: 0.8,
-        size: 1.2,
-        size_randomness: 0.6,
-        size_curve: Some(particles::Curve {
-            points: vec![(0.0, 0.0), (0.05, 1.0), (0.85, 0.8), (1.0, 0.0)],
-            ..Default::default()
-        }),
-        emission_shape: particles::EmissionShape::Rect {
-            width: cfg::ARENA_WIDTH,
-            height: cfg::ARENA_HEIGHT,
-        },
-        shape: particles::ParticleShape::Circle { subdivisions: 4 },
-        colors_curve: particles::ColorCurve {
-            start: palette::WHITE,
-            mid: palette::WHITE,
-            end: palette::WHITE,
-        },
-        ..Default::default()
-    }
-}
-
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
 pub enum GameState {
+    #[default]
     LevelLoading,
     LevelRunning,
     LevelCompleted,
     Pause,
     GameOver,
+    /// The ships are placed for the next versus round and wait for it to start.
+    RoundStarting,
+    RoundOver,
+    /// The results of a versus match, until a player asks for a rematch.
+    MatchOver,
+}
+
+/// Rule variations picked at launch and kept for the whole session.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct GameMode {
+    /// Asteroids bounce off each other instead of passing through.
+    pub bouncing_asteroids: bool,
+    /// Two players share the arena, each with their own ship, score and lives.
+    pub co_op: bool,
+    /// Players' bullets hit each other's ships.
+    pub friendly_fire: bool,
+    /// Two players shoot at each other for points over rounds, asteroids are only obstacles
+    /// and no aliens come.
+    pub versus: bool,
 }
 
-impl Default for GameState {
-    fn default() -> Self {
-        GameState::LevelLoading
+impl GameMode {
+    pub fn player_count(self) -> usize {
+        if self.co_op || self.versus {
+            2
+        } else {
+            1
+        }
+    }
+
+    pub fn is_friendly_fire(self) -> bool {
+        self.friendly_fire || self.versus
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum Action {
     Accelerate,
     TurnLeft,
@@ -360,4 +410,41 @@ pub enum Action {
     Shoot,
     TogglePause,
     ToggleDebugInfo,
+    Hyperspace,
+    Shield,
+}
+
+impl Action {
+    /// Every action, in declaration order. New actions go to the end to keep replays valid.
+    pub const ALL: &'static [Action] = &[
+        Action::Accelerate,
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::Shoot,
+        Action::TogglePause,
+        Action::ToggleDebugInfo,
+        Action::Hyperspace,
+        Action::Shield,
+    ];
+
+    /// Whether the action fires once per key press instead of while the key is held.
+    pub fn is_triggered_by_press(self) -> bool {
+        matches!(self, Action::TogglePause | Action::ToggleDebugInfo | Action::Hyperspace)
+    }
+}
+
+/// Analog controls of the ship, taking over from the turn actions while a heading is set.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Steering {
+    /// Where the ship should face in radians; it turns there no faster than with the keys.
+    pub heading: Option<f32>,
+    /// Share of the full thrust from 0 to 1, added to [`Action::Accelerate`].
+    pub thrust: f32,
+}
+
+/// What one player does in a step.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayerInput {
+    pub actions: HashSet<Action>,
+    pub steering: Option<Steering>,
 }
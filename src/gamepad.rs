@@ -0,0 +1,141 @@
+//! Gamepads, mapped to the same actions as the keyboard, with optional analog steering.
+//!
+//! Without the `gamepad` feature nothing is ever read from them.
+
+use crate::settings::GamepadSettings;
+use asteroids::entity::{Action, Steering};
+#[cfg(feature = "gamepad")]
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+
+#[cfg(feature = "gamepad")]
+#[rustfmt::skip]
+const BUTTON_MAP: &[(Button, Action)] = &[
+    (Button::DPadUp, Action::Accelerate),
+    (Button::DPadLeft, Action::TurnLeft),
+    (Button::DPadRight, Action::TurnRight),
+    (Button::DPadDown, Action::Hyperspace),
+    (Button::South, Action::Shoot),
+    (Button::East, Action::Hyperspace),
+    (Button::West, Action::Shield),
+    (Button::LeftTrigger, Action::Shield),
+    (Button::Start, Action::TogglePause),
+    (Button::Select, Action::ToggleDebugInfo),
+];
+#[cfg(feature = "gamepad")]
+const THRUST_TRIGGER: Button = Button::RightTrigger2;
+
+/// What the gamepad asks for this frame.
+#[derive(Default)]
+pub struct GamepadInput {
+    /// Actions of the buttons pressed since the last poll.
+    pub pressed: Vec<Action>,
+    /// Actions of the buttons being held, with the stick and trigger when they aren't analog.
+    pub held: Vec<Action>,
+    pub steering: Option<Steering>,
+}
+
+#[cfg(feature = "gamepad")]
+pub struct Gamepads {
+    /// `None` where gamepads aren't supported, the keyboard still works then.
+    gilrs: Option<Gilrs>,
+    /// The first pad plugged in; when it's unplugged another connected one takes over.
+    active: Option<GamepadId>,
+}
+
+#[cfg(feature = "gamepad")]
+impl Default for Gamepads {
+    fn default() -> Self {
+        let gilrs = Gilrs::new()
+            .map_err(|err| eprintln!("gamepads disabled: {}", err))
+            .ok();
+        let active = gilrs
+            .as_ref()
+            .and_then(|gilrs| gilrs.gamepads().next().map(|(id, _)| id));
+        Gamepads { gilrs, active }
+    }
+}
+
+#[cfg(feature = "gamepad")]
+impl Gamepads {
+    /// Goes through the pending events, so it's called every frame even if nothing is read.
+    pub fn poll(&mut self, settings: &GamepadSettings) -> GamepadInput {
+        let mut input = GamepadInput::default();
+        let gilrs = match &mut self.gilrs {
+            Some(gilrs) => gilrs,
+            None => return input,
+        };
+        while let Some(event) = gilrs.next_event() {
+            match event.event {
+                EventType::Connected if self.active.is_none() => {
+                    eprintln!("gamepad connected: {}", gilrs.gamepad(event.id).name());
+                    self.active = Some(event.id);
+                }
+                EventType::Disconnected if self.active == Some(event.id) => {
+                    eprintln!("gamepad disconnected: {}", gilrs.gamepad(event.id).name());
+                    self.active = gilrs
+                        .gamepads()
+                        .map(|(id, _)| id)
+                        .find(|&id| id != event.id);
+                }
+                EventType::ButtonPressed(button, _) if self.active == Some(event.id) => {
+                    input.pressed.extend(actions_of(button));
+                }
+                _ => (),
+            }
+        }
+        let gamepad = match self.active.and_then(|id| gilrs.connected_gamepad(id)) {
+            Some(gamepad) => gamepad,
+            None => return input,
+        };
+        for &(button, action) in BUTTON_MAP {
+            if gamepad.is_pressed(button) {
+                input.held.push(action);
+            }
+        }
+        let deadzone = settings.deadzone;
+        // the stick's y axis points up, the arena's down
+        let stick_x = gamepad.value(Axis::LeftStickX);
+        let stick_y = -gamepad.value(Axis::LeftStickY);
+        let trigger = gamepad
+            .button_data(THRUST_TRIGGER)
+            .map_or(0.0, |data| data.value());
+        let thrust = ((trigger - deadzone) / (1.0 - deadzone)).clamp(0.0, 1.0);
+        if settings.analog_steering {
+            let is_stick_tilted = stick_x.hypot(stick_y) > deadzone;
+            input.steering = Some(Steering {
+                heading: is_stick_tilted.then(|| stick_y.atan2(stick_x)),
+                thrust,
+            });
+        } else {
+            if stick_x < -deadzone {
+                input.held.push(Action::TurnLeft);
+            }
+            if stick_x > deadzone {
+                input.held.push(Action::TurnRight);
+            }
+            if thrust > 0.0 {
+                input.held.push(Action::Accelerate);
+            }
+        }
+        input
+    }
+}
+
+#[cfg(feature = "gamepad")]
+fn actions_of(button: Button) -> impl Iterator<Item = Action> {
+    BUTTON_MAP
+        .iter()
+        .filter(move |&&(b, _)| b == button)
+        .map(|&(_, action)| action)
+}
+
+#[cfg(not(feature = "gamepad"))]
+#[derive(Default)]
+pub struct Gamepads;
+
+#[cfg(not(feature = "gamepad"))]
+impl Gamepads {
+    pub fn poll(&mut self, _settings: &GamepadSettings) -> GamepadInput {
+        GamepadInput::default()
+    }
+}
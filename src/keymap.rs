@@ -0,0 +1,281 @@
+//! Which keys trigger which [`Action`]; every action may have several keys.
+//!
+//! In a settings file the keymap is a table from action names to key names:
+//!
+//! ```toml
+//! [keymap]
+//! accelerate = ["Up", "W"]
+//! shoot = ["S", "Space"]
+//! ```
+
+use crate::entity::Action;
+use macroquad::input::KeyCode;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+type KeymapTable = BTreeMap<String, Vec<String>>;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(try_from = "KeymapTable", into = "KeymapTable")]
+pub struct Keymap {
+    keys: HashMap<Action, Vec<KeyCode>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use Action::*;
+        let bindings = [
+            (Accelerate, KeyCode::Up),
+            (TurnLeft, KeyCode::Left),
+            (TurnRight, KeyCode::Right),
+            (Shoot, KeyCode::S),
+            (TogglePause, KeyCode::Escape),
+            (ToggleDebugInfo, KeyCode::D),
+            (Hyperspace, KeyCode::Down),
+            (Shield, KeyCode::A),
+        ];
+        let keys = bindings
+            .iter()
+            .map(|&(action, key)| (action, vec![key]))
+            .collect();
+        Keymap { keys }
+    }
+}
+
+impl Keymap {
+    /// Keys on the other side of the keyboard, clear of the first player's defaults.
+    pub fn second_player() -> Self {
+        use Action::*;
+        let bindings = [
+            (Accelerate, KeyCode::I),
+            (TurnLeft, KeyCode::J),
+            (TurnRight, KeyCode::L),
+            (Shoot, KeyCode::U),
+            (Hyperspace, KeyCode::K),
+            (Shield, KeyCode::O),
+        ];
+        let keys = bindings
+            .iter()
+            .map(|&(action, key)| (action, vec![key]))
+            .collect();
+        Keymap { keys }
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.keys.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Every key with the action it triggers, actions in [`Action::ALL`] order.
+    pub fn bindings(&self) -> impl Iterator<Item = (KeyCode, Action)> + '_ {
+        Action::ALL
+            .iter()
+            .flat_map(move |&action| self.keys(action).iter().map(move |&key| (key, action)))
+    }
+
+    /// Adds a key to the action, taking it away from any other action it triggered.
+    pub fn bind(&mut self, action: Action, key: KeyCode) -> Result<(), String> {
+        if self.keys(action).contains(&key) {
+            return Ok(());
+        }
+        self.unbind(key)?;
+        self.keys.entry(action).or_default().push(key);
+        Ok(())
+    }
+
+    /// Takes the key away from whatever action it triggers.
+    pub fn unbind(&mut self, key: KeyCode) -> Result<(), String> {
+        for (&action, keys) in &self.keys {
+            if keys.contains(&key) {
+                self.check_unbind(action, key)?;
+            }
+        }
+        for keys in self.keys.values_mut() {
+            keys.retain(|&k| k != key);
+        }
+        Ok(())
+    }
+
+    /// Removes the most recently added key of the action.
+    pub fn unbind_last(&mut self, action: Action) -> Result<(), String> {
+        if let Some(&key) = self.keys(action).last() {
+            self.check_unbind(action, key)?;
+            self.keys.get_mut(&action).unwrap().pop();
+        }
+        Ok(())
+    }
+
+    /// The game can't be unpaused without a key, every other action may go unbound.
+    fn check_unbind(&self, action: Action, key: KeyCode) -> Result<(), String> {
+        if action == Action::TogglePause && self.keys(action) == [key] {
+            Err(format!("{} needs a key", action_name(action)))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Starts from `keymap`, so the actions left out keep its keys.
+    fn from_table(table: KeymapTable, mut keymap: Keymap) -> Result<Self, String> {
+        for (name, key_names) in table {
+            let action = Action::ALL
+                .iter()
+                .copied()
+                .find(|&action| action_name(action) == name)
+                .ok_or_else(|| format!("unknown action `{}`", name))?;
+            let keys = key_names
+                .iter()
+                .map(|key_name| {
+                    key_from_name(key_name)
+                        .ok_or_else(|| format!("unknown key `{}` for action `{}`", key_name, name))
+                })
+                .collect::<Result<_, _>>()?;
+            keymap.keys.insert(action, keys);
+        }
+        for (key, action) in keymap.bindings() {
+            if let Some((_, other)) = keymap.bindings().find(|&(k, a)| k == key && a != action) {
+                return Err(format!(
+                    "key `{:?}` is bound to both `{}` and `{}`",
+                    key,
+                    action_name(action),
+                    action_name(other)
+                ));
+            }
+        }
+        Ok(keymap)
+    }
+}
+
+impl TryFrom<KeymapTable> for Keymap {
+    type Error = String;
+
+    fn try_from(table: KeymapTable) -> Result<Self, String> {
+        let keymap = Keymap::from_table(table, Keymap::default())?;
+        if keymap.keys(Action::TogglePause).is_empty() {
+            return Err(format!("action `{}` needs a key", action_name(Action::TogglePause)));
+        }
+        Ok(keymap)
+    }
+}
+
+/// For `deserialize_with`: actions left out keep the keys of [`Keymap::second_player`], and
+/// pausing is left to the first player.
+pub fn deserialize_second_player<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Keymap, D::Error> {
+    let table = KeymapTable::deserialize(deserializer)?;
+    Keymap::from_table(table, Keymap::second_player()).map_err(D::Error::custom)
+}
+
+impl From<Keymap> for KeymapTable {
+    fn from(keymap: Keymap) -> Self {
+        Action::ALL
+            .iter()
+            .map(|&action| {
+                let key_names = keymap.keys(action).iter().map(|key| format!("{:?}", key));
+                (action_name(action).to_string(), key_names.collect())
+            })
+            .collect()
+    }
+}
+
+/// The name of the action in settings files and on screen.
+pub fn action_name(action: Action) -> &'static str {
+    match action {
+        Action::Accelerate => "accelerate",
+        Action::TurnLeft => "turn_left",
+        Action::TurnRight => "turn_right",
+        Action::Shoot => "shoot",
+        Action::TogglePause => "toggle_pause",
+        Action::ToggleDebugInfo => "toggle_debug_info",
+        Action::Hyperspace => "hyperspace",
+        Action::Shield => "shield",
+    }
+}
+
+/// Keys are named like their [`KeyCode`] variant.
+fn key_from_name(name: &str) -> Option<KeyCode> {
+    KEYS.iter()
+        .copied()
+        .find(|key| format!("{:?}", key) == name)
+}
+
+#[rustfmt::skip]
+const KEYS: &[KeyCode] = {
+    use KeyCode::*;
+    &[
+        Space, Apostrophe, Comma, Minus, Period, Slash, Semicolon, Equal,
+        Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+        LeftBracket, Backslash, RightBracket, GraveAccent, World1, World2,
+        Escape, Enter, Tab, Backspace, Insert, Delete, Right, Left, Down, Up,
+        PageUp, PageDown, Home, End, CapsLock, ScrollLock, NumLock, PrintScreen, Pause,
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13,
+        F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24, F25,
+        Kp0, Kp1, Kp2, Kp3, Kp4, Kp5, Kp6, Kp7, Kp8, Kp9,
+        KpDecimal, KpDivide, KpMultiply, KpSubtract, KpAdd, KpEnter, KpEqual,
+        LeftShift, LeftControl, LeftAlt, LeftSuper, RightShift, RightControl, RightAlt, RightSuper,
+        Menu,
+    ]
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Settings {
+        keymap: Keymap,
+    }
+
+    fn parse(text: &str) -> Result<Keymap, String> {
+        toml::from_str::<Settings>(text)
+            .map(|settings| settings.keymap)
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn keymap_survives_toml_roundtrip() {
+        let mut keymap = Keymap::default();
+        keymap.bind(Action::Shoot, KeyCode::Space).unwrap();
+        let text = toml::to_string(&Settings { keymap: keymap.clone() }).unwrap();
+        assert_eq!(parse(&text), Ok(keymap));
+    }
+
+    #[test]
+    fn actions_take_several_keys_and_missing_ones_keep_defaults() {
+        let keymap = parse("[keymap]\naccelerate = [\"Up\", \"W\"]\n").unwrap();
+        assert_eq!(keymap.keys(Action::Accelerate), [KeyCode::Up, KeyCode::W]);
+        assert_eq!(keymap.keys(Action::Shoot), [KeyCode::S]);
+    }
+
+    #[test]
+    fn bad_keymaps_are_rejected() {
+        let err = parse("[keymap]\nshoot = [\"Spacebar\"]\n").unwrap_err();
+        assert!(err.contains("Spacebar"), "{}", err);
+        let err = parse("[keymap]\nfire = [\"S\"]\n").unwrap_err();
+        assert!(err.contains("fire"), "{}", err);
+        let err = parse("[keymap]\nshoot = [\"Up\"]\n").unwrap_err();
+        assert!(err.contains("accelerate") && err.contains("shoot"), "{}", err);
+        let err = parse("[keymap]\ntoggle_pause = []\n").unwrap_err();
+        assert!(err.contains("toggle_pause"), "{}", err);
+    }
+
+    #[test]
+    fn second_player_keymap_keeps_its_own_defaults_and_needs_no_pause() {
+        let mut deserializer = toml::Deserializer::new("shoot = [\"Y\"]\n");
+        let keymap = deserialize_second_player(&mut deserializer).unwrap();
+        assert_eq!(keymap.keys(Action::Shoot), [KeyCode::Y]);
+        assert_eq!(keymap.keys(Action::Accelerate), [KeyCode::I]);
+        assert!(keymap.keys(Action::TogglePause).is_empty());
+    }
+
+    #[test]
+    fn binding_a_key_takes_it_from_other_actions() {
+        let mut keymap = Keymap::default();
+        keymap.bind(Action::Shoot, KeyCode::Up).unwrap();
+        assert_eq!(keymap.keys(Action::Shoot), [KeyCode::S, KeyCode::Up]);
+        assert!(keymap.keys(Action::Accelerate).is_empty());
+        assert!(keymap.bind(Action::Shoot, KeyCode::Escape).is_err());
+        assert!(keymap.unbind_last(Action::TogglePause).is_err());
+        assert_eq!(keymap.keys(Action::TogglePause), [KeyCode::Escape]);
+    }
+}
@@ -0,0 +1,252 @@
+//! Headless simulation of the game: entities and the systems advancing them.
+//!
+//! Nothing here touches the window, so a [`Game`] can be stepped from tests or bots
+//! with an explicit set of [`entity::Action`]s.
+
+use macroquad::math;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+pub mod arena;
+pub mod cfg;
+pub mod config;
+pub mod entity;
+pub mod keymap;
+pub mod netplay;
+pub mod palette;
+pub mod replay;
+pub mod rng;
+mod sprites;
+mod systems;
+
+#[derive(Clone)]
+pub struct Game {
+    pub state: entity::GameState,
+    pub mode: entity::GameMode,
+    pub config: config::Config,
+    pub rng: rng::Rng,
+    /// Filled when a game starts, one per player of the [`entity::GameMode`].
+    pub players: Vec<entity::Player>,
+    pub break_timer: f32,
+    pub alien_timer: f32,
+    pub wave: u32,
+    pub last_explosion_id: u32,
+    pub bullets: Vec<entity::Bullet>,
+    pub asteroids: Vec<entity::Asteroid>,
+    pub explosions: Vec<entity::Explosion>,
+    pub aliens: Vec<entity::Alien>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        let config = config::Config::default();
+        Game {
+            state: Default::default(),
+            mode: Default::default(),
+            rng: Default::default(),
+            players: Vec::new(),
+            break_timer: 0.0,
+            alien_timer: config.aliens.spawn_period,
+            wave: 1,
+            last_explosion_id: 0,
+            bullets: Vec::new(),
+            asteroids: Vec::new(),
+            explosions: Vec::new(),
+            aliens: Vec::new(),
+            config,
+        }
+    }
+}
+
+impl Game {
+    pub fn new(seed: u64, config: config::Config) -> Self {
+        Game {
+            rng: rng::Rng::new(seed),
+            alien_timer: config.aliens.spawn_period,
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Swaps in new tuning values mid-game. Asteroids and the alien countdown are rescaled so
+    /// they move and count at the new rates straight away instead of on the next wave.
+    pub fn reconfigure(&mut self, config: config::Config) -> Result<(), String> {
+        let (old_stages, new_stages) = (&self.config.asteroids.stages, &config.asteroids.stages);
+        if old_stages.len() != new_stages.len() {
+            return Err(format!(
+                "asteroids.stages can't go from {} to {} stages while the game runs",
+                old_stages.len(),
+                new_stages.len()
+            ));
+        }
+        for asteroid in &mut self.asteroids {
+            let (old_stage, new_stage) = (&old_stages[asteroid.stage], &new_stages[asteroid.stage]);
+            asteroid.body.velocity *= new_stage.max_speed / old_stage.max_speed;
+            // the outline grows with the radius, it's what polygon collisions test against
+            let scale = new_stage.radius / old_stage.radius;
+            if let entity::cmpt::SpriteVariant::Vector { layers } = &mut asteroid.sprite.variant {
+                for point in layers.iter_mut().flat_map(|(points, _)| points) {
+                    *point *= scale;
+                }
+            }
+            asteroid.body.radius = new_stage.radius;
+        }
+        self.alien_timer *= config.aliens.spawn_period / self.config.aliens.spawn_period;
+        for ship in self.players.iter_mut().filter_map(|p| p.ship.as_mut()) {
+            ship.shield_energy = ship.shield_energy.min(config.shield.max_energy);
+            ship.body.radius = config.ship.hit_radius;
+        }
+        for alien in &mut self.aliens {
+            alien.body.radius = config.aliens.hit_radius_by_kind[alien.kind as usize];
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    /// Advances the simulation by one [`cfg::TIME_STEP`] with the first player's actions held.
+    pub fn step(&mut self, player_actions: &HashSet<entity::Action>) {
+        self.step_players(&[entity::PlayerInput {
+            actions: player_actions.clone(),
+            steering: None,
+        }]);
+    }
+
+    /// Like [`Game::step`], with an input for each player in order.
+    pub fn step_players(&mut self, inputs: &[entity::PlayerInput]) {
+        let dt = cfg::TIME_STEP;
+        for (player, input) in self.players.iter_mut().zip(inputs) {
+            player.actions.clone_from(&input.actions);
+            player.steering = input.steering;
+        }
+        systems::ai::update(self, dt);
+        systems::timers::update(self, dt);
+        systems::moving::update(self, dt);
+        systems::collision::update(self, dt);
+        systems::damage::update(self, dt);
+        systems::gamestate::update(self, dt);
+        systems::cleanup::update(self, dt);
+        systems::spawn::update(self, dt);
+    }
+
+    /// Cell size of the collision grids for the game as it is, for the collision benchmark.
+    pub fn collision_cell_size(&self) -> f32 {
+        systems::collision::cell_size(self)
+    }
+
+    pub fn ships(&self) -> impl Iterator<Item = &entity::Ship> {
+        self.players
+            .iter()
+            .filter_map(|player| player.ship.as_ref())
+    }
+
+    /// A hash of the simulated state, equal in two games that were fed the same inputs. Only
+    /// explosions are left out, nothing depends on them.
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        self.state.hash(&mut hasher);
+        self.rng.hash(&mut hasher);
+        self.wave.hash(&mut hasher);
+        hash_f32s(&mut hasher, &[self.break_timer, self.alien_timer]);
+        for player in &self.players {
+            (player.score, player.lives, player.kills, player.rounds_won).hash(&mut hasher);
+            hash_f32s(&mut hasher, &[player.respawn_timer]);
+            player.ship.is_some().hash(&mut hasher);
+            if let Some(ship) = &player.ship {
+                (ship.is_in_hyperspace, ship.is_shielded).hash(&mut hasher);
+                hash_body(&mut hasher, ship.position, ship.sprite.angle, &ship.body);
+                hash_f32s(
+                    &mut hasher,
+                    &[
+                        ship.weapon_cooldown_timer,
+                        ship.invulnerability_timer,
+                        ship.hyperspace_timer,
+                        ship.shield_energy,
+                    ],
+                );
+            }
+        }
+        self.bullets.len().hash(&mut hasher);
+        for bullet in &self.bullets {
+            hash_body(&mut hasher, bullet.position, bullet.sprite.angle, &bullet.body);
+            hash_f32s(&mut hasher, &[bullet.life_timer]);
+        }
+        self.asteroids.len().hash(&mut hasher);
+        for asteroid in &self.asteroids {
+            asteroid.stage.hash(&mut hasher);
+            hash_body(&mut hasher, asteroid.position, asteroid.sprite.angle, &asteroid.body);
+        }
+        self.aliens.len().hash(&mut hasher);
+        for alien in &self.aliens {
+            hash_body(&mut hasher, alien.position, alien.sprite.angle, &alien.body);
+            hash_f32s(&mut hasher, &[alien.weapon_cooldown_timer, alien.shift_timer]);
+        }
+        hasher.finish()
+    }
+}
+
+/// FNV-1a, unlike the std hasher it's sure to give the same result in every build.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Floats go in bit for bit, the simulation is deterministic down to the last one.
+fn hash_f32s(hasher: &mut Fnv1a, values: &[f32]) {
+    for value in values {
+        hasher.write_u32(value.to_bits());
+    }
+}
+
+fn hash_body(hasher: &mut Fnv1a, position: math::Vec2, angle: f32, body: &entity::cmpt::Body) {
+    let velocity = body.velocity;
+    hash_f32s(hasher, &[position.x, position.y, angle, velocity.x, velocity.y]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconfigure_rescales_asteroids_to_new_stage_speeds() {
+        let mut game = Game::new(1, config::Config::default());
+        for _ in 0..10 {
+            game.step(&HashSet::new());
+        }
+        let before: Vec<_> = game.asteroids.iter().map(|a| a.body.velocity).collect();
+        assert!(!before.is_empty());
+        let mut config = game.config.clone();
+        for stage in &mut config.asteroids.stages {
+            stage.max_speed *= 2.0;
+            stage.radius *= 1.5;
+        }
+        config.ship.hit_radius *= 1.5;
+        game.reconfigure(config).unwrap();
+        for (asteroid, velocity) in game.asteroids.iter().zip(before) {
+            assert!((asteroid.body.velocity - velocity * 2.0).length() < 1e-3);
+            let radius = game.config.asteroids.stages[asteroid.stage].radius;
+            assert_eq!(asteroid.body.radius, radius);
+        }
+        let ship = game.players[0].ship.as_ref().unwrap();
+        assert_eq!(ship.body.radius, game.config.ship.hit_radius);
+
+        let mut config = game.config.clone();
+        config.asteroids.stages.pop();
+        assert!(game.reconfigure(config).is_err());
+        assert_eq!(game.config.asteroids.stages.len(), 3);
+    }
+}
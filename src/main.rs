@@ -1,29 +1,241 @@
+use asteroids::{arena, cfg, config, entity, keymap, netplay, palette, replay};
 use macroquad::{material, miniquad::date, rand, time, window};
-use std::collections::HashSet;
+use std::{
+    fs, io,
+    net::{ToSocketAddrs, UdpSocket},
+    path::{Path, PathBuf},
+};
 
-mod cfg;
-mod entity;
-mod palette;
-mod sprites;
-mod systems;
+mod app;
+mod gamepad;
+mod pointer;
+mod render;
+mod settings;
+mod watch;
+
+const CONFIG_FILE_NAME: &str = "asteroids.toml";
+
+#[derive(Default)]
+struct Options {
+    seed: Option<u64>,
+    record: Option<String>,
+    replay: Option<String>,
+    config: Option<String>,
+    mode: entity::GameMode,
+    host: Option<u16>,
+    join: Option<String>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                let value = args.next().ok_or("--seed requires a value")?;
+                let seed = value
+                    .parse()
+                    .map_err(|_| format!("invalid --seed value: {}", value))?;
+                options.seed = Some(seed);
+            }
+            "--record" => options.record = Some(args.next().ok_or("--record requires a file")?),
+            "--replay" => options.replay = Some(args.next().ok_or("--replay requires a file")?),
+            "--config" => options.config = Some(args.next().ok_or("--config requires a file")?),
+            "--bouncing-asteroids" => options.mode.bouncing_asteroids = true,
+            "--co-op" => options.mode.co_op = true,
+            "--friendly-fire" => options.mode.friendly_fire = true,
+            "--versus" => options.mode.versus = true,
+            "--host" => {
+                let value = args.next().ok_or("--host requires a port")?;
+                let port = value
+                    .parse()
+                    .map_err(|_| format!("invalid --host port: {}", value))?;
+                options.host = Some(port);
+            }
+            "--join" => options.join = Some(args.next().ok_or("--join requires an address")?),
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+    if options.mode.co_op && options.mode.versus {
+        return Err("--co-op and --versus can't be played together".to_string());
+    }
+    let is_netplay = options.host.is_some() || options.join.is_some();
+    if options.host.is_some() && options.join.is_some() {
+        return Err("--host and --join can't be used together".to_string());
+    }
+    if is_netplay && (options.record.is_some() || options.replay.is_some()) {
+        return Err("a network game can't be recorded or replayed".to_string());
+    }
+    if options.host.is_some() && options.mode.player_count() != 2 {
+        return Err("--host requires --co-op or --versus".to_string());
+    }
+    Ok(options)
+}
+
+/// The file given with `--config`, or the one next to the binary, which may not exist yet.
+fn config_path(option: Option<String>) -> Option<PathBuf> {
+    option.map(PathBuf::from).or_else(|| {
+        Some(
+            std::env::current_exe()
+                .ok()?
+                .with_file_name(CONFIG_FILE_NAME),
+        )
+    })
+}
+
+/// Starts looking for the other player of a network game; who joins plays the host's game.
+fn connect(
+    host: Option<u16>,
+    join: Option<&str>,
+    seed: u64,
+    mode: entity::GameMode,
+    config: &config::Config,
+) -> Result<Option<netplay::Connecting>, String> {
+    if let Some(port) = host {
+        eprintln!("waiting for a player to join on port {}", port);
+        let socket = UdpSocket::bind(("0.0.0.0", port))
+            .map_err(|err| format!("can't host on port {}: {}", port, err))?;
+        let connecting = netplay::Connecting::host(socket, seed, mode, config)
+            .map_err(|err| format!("can't host: {}", err))?;
+        return Ok(Some(connecting));
+    }
+    if let Some(address) = join {
+        let host = address
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or_else(|| format!("invalid --join address: {}", address))?;
+        eprintln!("joining {}", host);
+        let socket = UdpSocket::bind(("0.0.0.0", 0))
+            .map_err(|err| format!("can't join {}: {}", address, err))?;
+        let connecting = netplay::Connecting::join(socket, host)
+            .map_err(|err| format!("can't join {}: {}", address, err))?;
+        return Ok(Some(connecting));
+    }
+    Ok(None)
+}
+
+fn read_config(path: &Path) -> Result<config::Config, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("can't read config {}: {}", path.display(), err))?;
+    config::Config::from_toml(&text)
+        .map_err(|err| format!("bad config {}: {}", path.display(), err))
+}
 
 #[derive(Default)]
-pub struct Game {
-    state: entity::GameState,
-    renderer: entity::Renderer,
-    player_actions: HashSet<entity::Action>,
-    break_timer: f32,
-    alien_timer: f32,
-    star_bg: entity::StarBackground,
-    ship: Option<entity::Ship>,
-    bullets: Vec<entity::Bullet>,
-    asteroids: Vec<entity::Asteroid>,
-    explosions: Vec<entity::Explosion>,
-    aliens: Vec<entity::Alien>,
+pub struct App {
+    game: asteroids::Game,
+    renderer: render::Renderer,
+    /// One for each player, the first also takes the gamepad and pointer.
+    player_inputs: Vec<entity::PlayerInput>,
+    step_accumulator: f32,
+    recorder: Option<replay::Recorder<io::BufWriter<fs::File>>>,
+    replay_steps: Option<std::vec::IntoIter<Vec<entity::PlayerInput>>>,
+    config_watcher: Option<watch::ConfigWatcher>,
+    settings: settings::Settings,
+    settings_path: Option<PathBuf>,
+    controls_screen: Option<settings::ControlsScreen>,
+    gamepads: gamepad::Gamepads,
+    pointer: Option<pointer::Pointer>,
+    netplay_connecting: Option<netplay::Connecting>,
+    netplay: Option<netplay::Session>,
+    /// Why the network game ended, the game stays frozen under it.
+    netplay_error: Option<String>,
+    star_bg: render::StarBackground,
+}
+
+/// Starts the network game once the other player is there.
+fn poll_connecting(app: &mut App) {
+    let result = match &mut app.netplay_connecting {
+        Some(connecting) => connecting.poll(),
+        None => return,
+    };
+    match result {
+        Ok(Some((session, game))) => {
+            eprintln!("the other player is here");
+            app.game = game;
+            app.netplay = Some(session);
+            app.netplay_connecting = None;
+        }
+        Ok(None) => (),
+        Err(err) => stop_netplay(app, err),
+    }
+}
+
+fn stop_netplay(app: &mut App, err: io::Error) {
+    eprintln!("network game stopped: {}", err);
+    app.netplay_connecting = None;
+    app.netplay = None;
+    app.netplay_error = Some(err.to_string());
+}
+
+fn step(app: &mut App) {
+    // only half of the players are here
+    if app.netplay_connecting.is_some() || app.netplay_error.is_some() {
+        return;
+    }
+    if let Some(session) = &mut app.netplay {
+        let local_input = &app.player_inputs[0];
+        match session
+            .poll()
+            .and_then(|()| session.advance(&mut app.game, local_input))
+        {
+            Ok(true) => (),
+            // the other player is behind, the input waits for the next step
+            Ok(false) => return,
+            Err(err) => {
+                stop_netplay(app, err);
+                return;
+            }
+        }
+    } else {
+        if let Some(steps) = &mut app.replay_steps {
+            match steps.next() {
+                Some(inputs) => app.player_inputs = inputs,
+                // the replay is over, the player takes control from here
+                None => app.replay_steps = None,
+            }
+        }
+        app.game.step_players(&app.player_inputs);
+        if let Some(recorder) = &mut app.recorder {
+            if let Err(err) = recorder.record(&app.player_inputs) {
+                eprintln!("recording stopped: {}", err);
+                app.recorder = None;
+            }
+        }
+    }
+    for input in &mut app.player_inputs {
+        input
+            .actions
+            .retain(|action| !action.is_triggered_by_press());
+    }
 }
 
-fn load(game: &mut Game) {
-    game.renderer.crt_effect = Some(
+/// Applies an edited config file. A replay stores one config for the whole run, so a
+/// recording can't follow the change and ends here with what it has so far.
+fn reload_config(app: &mut App) {
+    let result = match app
+        .config_watcher
+        .as_mut()
+        .and_then(|watcher| watcher.poll())
+    {
+        Some(Ok(config)) if config == app.game.config => return,
+        Some(result) => result.and_then(|config| app.game.reconfigure(config)),
+        None => return,
+    };
+    match result {
+        Ok(()) => {
+            eprintln!("config reloaded");
+            if app.recorder.take().is_some() {
+                eprintln!("recording stopped: the config changed");
+            }
+        }
+        Err(err) => eprintln!("config not reloaded: {}", err),
+    }
+}
+
+fn load(app: &mut App) {
+    app.renderer.crt_effect = Some(
         material::load_material(
             include_str!("crt.vert"),
             include_str!("crt.frag"),
@@ -31,7 +243,6 @@ fn load(game: &mut Game) {
         )
         .unwrap(),
     );
-    game.alien_timer = cfg::ALIEN_SPAWN_PERIOD;
 }
 
 fn window_conf() -> window::Conf {
@@ -45,21 +256,102 @@ fn window_conf() -> window::Conf {
 
 #[macroquad::main(window_conf)]
 async fn main() {
-    rand::srand(date::now() as u64);
-    let mut game = Default::default();
-    load(&mut game);
+    let options = parse_args().unwrap_or_else(|err| {
+        exit_with_error(format!(
+            "{}\nusage: asteroids [--seed <number>] [--record <file>] [--replay <file>] \
+             [--config <file>] [--bouncing-asteroids] [--co-op] [--friendly-fire] \
+             [--versus] [--host <port>] [--join <address>]",
+            err
+        ))
+    });
+    let replay = options.replay.map(|path| {
+        fs::File::open(&path)
+            .and_then(|file| replay::Replay::read(io::BufReader::new(file)))
+            .unwrap_or_else(|err| exit_with_error(format!("can't read replay {}: {}", path, err)))
+    });
+    let seed = replay
+        .as_ref()
+        .map(|replay| replay.seed)
+        .or(options.seed)
+        .unwrap_or_else(|| date::now() as u64);
+    // a replay is only faithful in the mode and config it was recorded with
+    let mode = replay.as_ref().map_or(options.mode, |replay| replay.mode);
+    let is_config_required = options.config.is_some();
+    let config_path = config_path(options.config);
+    let config = match (&replay, &config_path) {
+        (Some(replay), _) => replay.config.clone(),
+        (None, Some(path)) if is_config_required || path.exists() => {
+            read_config(path).unwrap_or_else(|err| exit_with_error(err))
+        }
+        (None, _) => Default::default(),
+    };
+    let netplay_connecting = connect(options.host, options.join.as_deref(), seed, mode, &config)
+        .unwrap_or_else(|err| exit_with_error(err));
+    // edits would steer a replay away from what was recorded, or the other player's game
+    let config_watcher = config_path
+        .filter(|_| replay.is_none() && netplay_connecting.is_none())
+        .map(watch::ConfigWatcher::new);
+    let recorder = options.record.map(|path| {
+        fs::File::create(&path)
+            .and_then(|file| replay::Recorder::new(io::BufWriter::new(file), seed, mode, &config))
+            .unwrap_or_else(|err| exit_with_error(format!("can't record to {}: {}", path, err)))
+    });
+    let settings_path = std::env::current_exe()
+        .ok()
+        .map(|exe| exe.with_file_name(settings::SETTINGS_FILE_NAME));
+    let settings = settings_path
+        .as_deref()
+        .map_or(Ok(Default::default()), settings::Settings::load)
+        .unwrap_or_else(|err| exit_with_error(err));
+    rand::srand(seed);
+    // a network game is replaced by the host's once the other player is there
+    let game = asteroids::Game {
+        mode,
+        ..asteroids::Game::new(seed, config)
+    };
+    // only the local player's input in a network game
+    let player_count = match netplay_connecting {
+        Some(_) => 1,
+        None => game.mode.player_count(),
+    };
+    let mut app = App {
+        game,
+        player_inputs: vec![Default::default(); player_count],
+        netplay_connecting,
+        recorder,
+        replay_steps: replay.map(|replay| replay.steps.into_iter()),
+        config_watcher,
+        pointer: match settings.control_scheme {
+            settings::ControlScheme::Pointer => Some(Default::default()),
+            settings::ControlScheme::Keys => None,
+        },
+        settings,
+        settings_path,
+        ..Default::default()
+    };
+    load(&mut app);
     loop {
-        let delta_time = time::get_frame_time();
-        systems::input::update(&mut game, delta_time);
-        systems::ai::update(&mut game, delta_time);
-        systems::timers::update(&mut game, delta_time);
-        systems::moving::update(&mut game, delta_time);
-        systems::collision::update(&mut game, delta_time);
-        systems::damage::update(&mut game, delta_time);
-        systems::gamestate::update(&mut game, delta_time);
-        systems::cleanup::update(&mut game, delta_time);
-        systems::spawn::update(&mut game, delta_time);
-        systems::draw::update(&mut game, delta_time);
+        let delta_time = f32::min(time::get_frame_time(), cfg::MAX_FRAME_TIME);
+        reload_config(&mut app);
+        poll_connecting(&mut app);
+        app::input::update(&mut app, delta_time);
+        app.step_accumulator += delta_time;
+        while app.step_accumulator >= cfg::TIME_STEP {
+            app.step_accumulator -= cfg::TIME_STEP;
+            step(&mut app);
+        }
+        if let Some(recorder) = &mut app.recorder {
+            if let Err(err) = recorder.flush() {
+                eprintln!("recording stopped: {}", err);
+                app.recorder = None;
+            }
+        }
+        app::draw::update(&mut app, delta_time);
         window::next_frame().await;
     }
 }
+
+fn exit_with_error(message: String) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(2);
+}
@@ -0,0 +1,575 @@
+//! Two instances playing one game over UDP, each with one of the players.
+//!
+//! A step is simulated as soon as the local input is known, guessing that the other player
+//! still holds what they held last. When their real input turns out different, the game is
+//! rolled back to the last step both inputs were known for and simulated again up to the
+//! present. Both sides send [`Game::checksum`]s of those confirmed steps to catch a desync.
+//!
+//! Packets start with `ASTN` magic and a kind byte:
+//! - hello, sent by the joining player until the host answers,
+//! - welcome, the host's answer: a replay header with the seed, mode and config, see
+//!   [`crate::replay`],
+//! - inputs: the step the sender needs the next input for and the step of the first input
+//!   sent as little-endian `u32`s, the number of inputs as a byte, the inputs encoded like in
+//!   a replay, then a byte set to 1 when the step and checksum of the sender's latest
+//!   confirmed checksum follow as little-endian `u32` and `u64`.
+
+use crate::{
+    config::Config,
+    entity::{GameMode, PlayerInput},
+    replay, Game,
+};
+use std::{
+    collections::VecDeque,
+    io::{self, Read},
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+const MAGIC: &[u8; 4] = b"ASTN";
+const HELLO: u8 = 0;
+const WELCOME: u8 = 1;
+const INPUTS: u8 = 2;
+const MAX_PACKET_SIZE: usize = 65507;
+const HELLO_PERIOD: Duration = Duration::from_millis(500);
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+/// Silence after which the other player is taken as gone, packets come every frame.
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+/// Steps simulated on guesses before waiting for the other player, a quarter second.
+const MAX_PREDICTION: u32 = 30;
+const MAX_INPUTS_PER_PACKET: usize = 64;
+/// Steps between checksums, one second.
+const CHECKSUM_PERIOD: u32 = 120;
+const CHECKSUMS_KEPT: usize = 8;
+
+pub struct Session {
+    socket: UdpSocket,
+    /// 0 for the host, 1 for the player who joined.
+    pub local_player: usize,
+    /// The game as of `confirmed_step`, up to which the inputs of both players are known.
+    confirmed: Game,
+    confirmed_step: u32,
+    /// Steps simulated so far, the ones past `confirmed_step` on guessed inputs.
+    step: u32,
+    /// Local inputs from `confirmed_step` to `step`.
+    local_inputs: VecDeque<PlayerInput>,
+    /// Inputs of the other player from `confirmed_step` on, they may run past `step`.
+    remote_inputs: VecDeque<PlayerInput>,
+    /// What the simulation took as the other player's inputs from `confirmed_step` to `step`.
+    used_remote_inputs: VecDeque<PlayerInput>,
+    /// The latest input of the other player, the guess for the steps still to come.
+    last_remote_input: PlayerInput,
+    is_rollback_needed: bool,
+    /// Local inputs from `unacked_step` on, sent until the other player has them.
+    unacked_inputs: VecDeque<PlayerInput>,
+    unacked_step: u32,
+    /// The latest checksums of the confirmed game by step.
+    checksums: VecDeque<(u32, u64)>,
+    /// A checksum of the other player for a step not confirmed here yet.
+    remote_checksum: Option<(u32, u64)>,
+    desync_step: Option<u32>,
+    /// Sent again on every hello in case it was lost, only the host has one.
+    welcome: Option<Vec<u8>>,
+    last_received: Instant,
+}
+
+/// A network game being set up, polled every frame until the other player is there.
+pub struct Connecting {
+    socket: UdpSocket,
+    /// The game to play and the welcome telling it, only the host knows them up front.
+    hosted: Option<(Game, Vec<u8>)>,
+    started: Instant,
+    last_hello: Option<Instant>,
+}
+
+impl Connecting {
+    /// Waits for a player to join and sends them the game to play.
+    pub fn host(socket: UdpSocket, seed: u64, mode: GameMode, config: &Config) -> io::Result<Self> {
+        if mode.player_count() != 2 {
+            return Err(invalid_input("only a game for two players can be hosted"));
+        }
+        let mut welcome = packet(WELCOME);
+        replay::write_header(&mut welcome, seed, mode, config)?;
+        let game = Game {
+            mode,
+            ..Game::new(seed, config.clone())
+        };
+        Connecting::new(socket, Some((game, welcome)))
+    }
+
+    /// Says hello to the host until it answers with the game to play.
+    pub fn join(socket: UdpSocket, host: SocketAddr) -> io::Result<Self> {
+        socket.connect(host)?;
+        Connecting::new(socket, None)
+    }
+
+    fn new(socket: UdpSocket, hosted: Option<(Game, Vec<u8>)>) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        Ok(Connecting {
+            socket,
+            hosted,
+            started: Instant::now(),
+            last_hello: None,
+        })
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.hosted.is_some()
+    }
+
+    /// Returns the session with the starting game, the same on both sides, once the other
+    /// player is there. Joining gives up when the host doesn't answer for a while.
+    pub fn poll(&mut self) -> io::Result<Option<(Session, Game)>> {
+        match &self.hosted {
+            Some(_) => self.poll_host(),
+            None => self.poll_join(),
+        }
+    }
+
+    fn poll_host(&mut self) -> io::Result<Option<(Session, Game)>> {
+        let mut buffer = vec![0; MAX_PACKET_SIZE];
+        loop {
+            let (size, peer) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(err) if is_transient(&err) => return Ok(None),
+                Err(err) => return Err(err),
+            };
+            if buffer[..size] == packet(HELLO) {
+                let (game, welcome) = self.hosted.take().unwrap();
+                self.socket.connect(peer)?;
+                send(&self.socket, &welcome)?;
+                let session =
+                    Session::new(self.socket.try_clone()?, 0, game.clone(), Some(welcome));
+                return Ok(Some((session, game)));
+            }
+        }
+    }
+
+    fn poll_join(&mut self) -> io::Result<Option<(Session, Game)>> {
+        if self.started.elapsed() >= JOIN_TIMEOUT {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "the host didn't answer"));
+        }
+        if self
+            .last_hello
+            .is_none_or(|hello| hello.elapsed() >= HELLO_PERIOD)
+        {
+            self.last_hello = Some(Instant::now());
+            match send(&self.socket, &packet(HELLO)) {
+                Err(err) if err.kind() != io::ErrorKind::ConnectionRefused => return Err(err),
+                _ => (),
+            }
+        }
+        let mut buffer = vec![0; MAX_PACKET_SIZE];
+        loop {
+            match self.socket.recv(&mut buffer) {
+                Ok(size) if buffer[..size].starts_with(&packet(WELCOME)) => {
+                    let (seed, mode, config) = replay::read_header(&buffer[5..size])?;
+                    if mode.player_count() != 2 {
+                        return Err(replay::invalid_data("the host's game isn't for two players"));
+                    }
+                    let game = Game { mode, ..Game::new(seed, config) };
+                    let session = Session::new(self.socket.try_clone()?, 1, game.clone(), None);
+                    return Ok(Some((session, game)));
+                }
+                Ok(_) => (),
+                // the host isn't up yet
+                Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => (),
+                Err(err) if is_transient(&err) => return Ok(None),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl Session {
+    fn new(socket: UdpSocket, local_player: usize, game: Game, welcome: Option<Vec<u8>>) -> Self {
+        Session {
+            socket,
+            local_player,
+            confirmed: game,
+            confirmed_step: 0,
+            step: 0,
+            local_inputs: VecDeque::new(),
+            remote_inputs: VecDeque::new(),
+            used_remote_inputs: VecDeque::new(),
+            last_remote_input: PlayerInput::default(),
+            is_rollback_needed: false,
+            unacked_inputs: VecDeque::new(),
+            unacked_step: 0,
+            checksums: VecDeque::new(),
+            remote_checksum: None,
+            desync_step: None,
+            welcome,
+            last_received: Instant::now(),
+        }
+    }
+
+    /// Takes in everything the other player sent since the last call. Fails once they quit
+    /// or stay silent for too long.
+    pub fn poll(&mut self) -> io::Result<()> {
+        let mut buffer = vec![0; MAX_PACKET_SIZE];
+        loop {
+            let size = match self.socket.recv(&mut buffer) {
+                Ok(size) => size,
+                Err(err) if is_transient(&err) => {
+                    if self.last_received.elapsed() >= PEER_TIMEOUT {
+                        let message = "the other player stopped answering";
+                        return Err(io::Error::new(io::ErrorKind::TimedOut, message));
+                    }
+                    return Ok(());
+                }
+                Err(err) => return Err(peer_error(err)),
+            };
+            let packet = &buffer[..size];
+            if packet.len() < 5 || &packet[..4] != MAGIC {
+                continue;
+            }
+            self.last_received = Instant::now();
+            match packet[4] {
+                HELLO => {
+                    if let Some(welcome) = &self.welcome {
+                        send(&self.socket, welcome).map_err(peer_error)?;
+                    }
+                }
+                INPUTS => self.receive_inputs(&packet[5..])?,
+                // a welcome sent again
+                _ => (),
+            }
+        }
+    }
+
+    /// Simulates the next step with the local input, or returns `false` without simulating
+    /// while the other player is too far behind.
+    pub fn advance(&mut self, game: &mut Game, local_input: &PlayerInput) -> io::Result<bool> {
+        self.confirm();
+        if self.is_rollback_needed {
+            self.roll_back(game);
+        }
+        let is_waiting = self.is_waiting();
+        if !is_waiting {
+            let remote_input = self.remote_input((self.step - self.confirmed_step) as usize);
+            game.step_players(&self.in_player_order(local_input, &remote_input));
+            self.local_inputs.push_back(local_input.clone());
+            self.unacked_inputs.push_back(local_input.clone());
+            self.used_remote_inputs.push_back(remote_input);
+            self.step += 1;
+            self.confirm();
+        }
+        self.send_inputs()?;
+        Ok(!is_waiting)
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.step - self.confirmed_step >= MAX_PREDICTION
+    }
+
+    pub fn step(&self) -> u32 {
+        self.step
+    }
+
+    pub fn confirmed_step(&self) -> u32 {
+        self.confirmed_step
+    }
+
+    /// The latest checksum of the confirmed game, with its step.
+    pub fn checksum(&self) -> Option<(u32, u64)> {
+        self.checksums.back().copied()
+    }
+
+    /// The first checksum step where the two games were found to differ.
+    pub fn desync_step(&self) -> Option<u32> {
+        self.desync_step
+    }
+
+    /// Moves the confirmed game along the steps both inputs are known for.
+    fn confirm(&mut self) {
+        while self.confirmed_step < self.step && !self.remote_inputs.is_empty() {
+            let local_input = self.local_inputs.pop_front().unwrap();
+            let remote_input = self.remote_inputs.pop_front().unwrap();
+            self.used_remote_inputs.pop_front();
+            let inputs = self.in_player_order(&local_input, &remote_input);
+            self.confirmed.step_players(&inputs);
+            self.confirmed_step += 1;
+            if self.confirmed_step.is_multiple_of(CHECKSUM_PERIOD) {
+                self.add_checksum(self.confirmed_step, self.confirmed.checksum());
+            }
+        }
+    }
+
+    /// Simulates again from the confirmed game with the inputs known by now.
+    fn roll_back(&mut self, game: &mut Game) {
+        *game = self.confirmed.clone();
+        for i in 0..self.local_inputs.len() {
+            let remote_input = self.remote_input(i);
+            game.step_players(&self.in_player_order(&self.local_inputs[i], &remote_input));
+            self.used_remote_inputs[i] = remote_input;
+        }
+        self.is_rollback_needed = false;
+    }
+
+    /// The other player's input `i` steps after the confirmed one, guessed when it isn't
+    /// known yet. Presses don't repeat, so they are left out of the guess.
+    fn remote_input(&self, i: usize) -> PlayerInput {
+        self.remote_inputs.get(i).cloned().unwrap_or_else(|| {
+            let mut guess = self.last_remote_input.clone();
+            guess
+                .actions
+                .retain(|action| !action.is_triggered_by_press());
+            guess
+        })
+    }
+
+    fn in_player_order(
+        &self,
+        local_input: &PlayerInput,
+        remote_input: &PlayerInput,
+    ) -> Vec<PlayerInput> {
+        let mut inputs = vec![local_input.clone(), remote_input.clone()];
+        if self.local_player == 1 {
+            inputs.reverse();
+        }
+        inputs
+    }
+
+    fn receive_inputs(&mut self, mut data: &[u8]) -> io::Result<()> {
+        let acked_step = read_u32(&mut data)?;
+        let first_step = read_u32(&mut data)?;
+        let mut count = [0];
+        data.read_exact(&mut count)?;
+        while self.unacked_step < acked_step && !self.unacked_inputs.is_empty() {
+            self.unacked_inputs.pop_front();
+            self.unacked_step += 1;
+        }
+        for step in (first_step..).take(count[0] as usize) {
+            let mut actions = [0];
+            data.read_exact(&mut actions)?;
+            let input = replay::read_input(actions[0], &mut data)?;
+            // inputs known already, or past a lost packet and sent again later
+            if step != self.confirmed_step + self.remote_inputs.len() as u32 {
+                continue;
+            }
+            let i = (step - self.confirmed_step) as usize;
+            if self
+                .used_remote_inputs
+                .get(i)
+                .is_some_and(|used| *used != input)
+            {
+                self.is_rollback_needed = true;
+            }
+            self.last_remote_input = input.clone();
+            self.remote_inputs.push_back(input);
+        }
+        let mut has_checksum = [0];
+        data.read_exact(&mut has_checksum)?;
+        if has_checksum[0] == 1 {
+            let step = read_u32(&mut data)?;
+            let mut checksum = [0; 8];
+            data.read_exact(&mut checksum)?;
+            self.compare_checksum(step, u64::from_le_bytes(checksum));
+        }
+        Ok(())
+    }
+
+    fn add_checksum(&mut self, step: u32, checksum: u64) {
+        if self.checksums.len() == CHECKSUMS_KEPT {
+            self.checksums.pop_front();
+        }
+        self.checksums.push_back((step, checksum));
+        if let Some((remote_step, remote_checksum)) = self.remote_checksum {
+            if remote_step == step {
+                self.remote_checksum = None;
+                self.compare_checksum(remote_step, remote_checksum);
+            }
+        }
+    }
+
+    fn compare_checksum(&mut self, step: u32, remote_checksum: u64) {
+        match self.checksums.iter().find(|&&(s, _)| s == step) {
+            Some(&(_, checksum)) if checksum != remote_checksum => {
+                self.desync_step = self.desync_step.or(Some(step));
+            }
+            Some(_) => (),
+            None if step > self.confirmed_step => {
+                self.remote_checksum = Some((step, remote_checksum))
+            }
+            None => (),
+        }
+    }
+
+    fn send_inputs(&self) -> io::Result<()> {
+        let mut packet = packet(INPUTS);
+        let needed_step = self.confirmed_step + self.remote_inputs.len() as u32;
+        packet.extend_from_slice(&needed_step.to_le_bytes());
+        packet.extend_from_slice(&self.unacked_step.to_le_bytes());
+        let inputs = self.unacked_inputs.iter().take(MAX_INPUTS_PER_PACKET);
+        packet.push(inputs.len() as u8);
+        for input in inputs {
+            replay::write_input(&mut packet, input)?;
+        }
+        match self.checksum() {
+            Some((step, checksum)) => {
+                packet.push(1);
+                packet.extend_from_slice(&step.to_le_bytes());
+                packet.extend_from_slice(&checksum.to_le_bytes());
+            }
+            None => packet.push(0),
+        }
+        send(&self.socket, &packet).map_err(peer_error)
+    }
+}
+
+fn packet(kind: u8) -> Vec<u8> {
+    let mut packet = MAGIC.to_vec();
+    packet.push(kind);
+    packet
+}
+
+/// A packet that can't go out is as good as lost, UDP makes no promises anyway.
+fn send(socket: &UdpSocket, packet: &[u8]) -> io::Result<()> {
+    match socket.send(packet) {
+        Err(err) if !is_transient(&err) => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Nothing to read or no room to send for now.
+fn is_transient(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::WouldBlock
+}
+
+/// A refused packet means the other player's game is gone.
+fn peer_error(err: io::Error) -> io::Error {
+    match err.kind() {
+        io::ErrorKind::ConnectionRefused => {
+            io::Error::new(io::ErrorKind::ConnectionRefused, "the other player left")
+        }
+        _ => err,
+    }
+}
+
+fn read_u32(mut reader: impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::Action;
+    use std::thread;
+
+    /// Inputs that change often, so guesses turn out wrong and games get rolled back.
+    fn scripted_input(player_id: usize, step: u32) -> PlayerInput {
+        let mut input = PlayerInput::default();
+        if (step / 40 + player_id as u32).is_multiple_of(2) {
+            input.actions.insert(Action::TurnLeft);
+        }
+        if step % 25 < 10 {
+            input.actions.insert(Action::Accelerate);
+        }
+        if (step + 7 * player_id as u32).is_multiple_of(13) {
+            input.actions.insert(Action::Shoot);
+        }
+        input
+    }
+
+    fn connected_sessions() -> [(Session, Game); 2] {
+        let host_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let host_address = host_socket.local_addr().unwrap();
+        let mode = GameMode { co_op: true, ..Default::default() };
+        let mut host = Connecting::host(host_socket, 7, mode, &Config::default()).unwrap();
+        let join_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut join = Connecting::join(join_socket, host_address).unwrap();
+        let (mut hosted, mut joined) = (None, None);
+        while hosted.is_none() || joined.is_none() {
+            if hosted.is_none() {
+                hosted = host.poll().unwrap();
+            }
+            if joined.is_none() {
+                joined = join.poll().unwrap();
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        [hosted.unwrap(), joined.unwrap()]
+    }
+
+    /// Runs both sides until they confirmed `steps`, returns false if they got stuck.
+    fn play(sessions: &mut [(Session, Game); 2], steps: u32) -> bool {
+        for _ in 0..100_000 {
+            if sessions
+                .iter()
+                .all(|(session, _)| session.confirmed_step >= steps)
+            {
+                return true;
+            }
+            for (session, game) in sessions.iter_mut() {
+                session.poll().unwrap();
+                let input = scripted_input(session.local_player, session.step);
+                session.advance(game, &input).unwrap();
+            }
+            if sessions.iter().all(|(session, _)| session.is_waiting()) {
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn sessions_over_localhost_agree_with_a_local_game() {
+        let mut sessions = connected_sessions();
+        assert!(play(&mut sessions, 600));
+        for (session, _) in &sessions {
+            assert_eq!(session.desync_step, None);
+            let mut game = Game {
+                mode: session.confirmed.mode,
+                ..Game::new(7, Config::default())
+            };
+            for step in 0..session.confirmed_step {
+                game.step_players(&[scripted_input(0, step), scripted_input(1, step)]);
+            }
+            assert_eq!(session.confirmed.checksum(), game.checksum());
+        }
+    }
+
+    #[test]
+    fn diverging_games_are_reported() {
+        let mut sessions = connected_sessions();
+        assert!(play(&mut sessions, 60));
+        sessions[1].0.confirmed.rng = crate::rng::Rng::new(8);
+        assert!(play(&mut sessions, 3 * CHECKSUM_PERIOD));
+        assert!(sessions
+            .iter()
+            .all(|(session, _)| session.desync_step.is_some()));
+    }
+
+    #[test]
+    fn the_session_ends_when_the_other_player_leaves() {
+        let [(mut session, mut game), other] = connected_sessions();
+        drop(other);
+        let input = PlayerInput::default();
+        let err = (0..1000)
+            .find_map(|_| {
+                thread::sleep(Duration::from_millis(1));
+                session
+                    .poll()
+                    .and_then(|()| session.advance(&mut game, &input))
+                    .err()
+            })
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+    }
+
+    #[test]
+    fn the_session_ends_when_the_other_player_goes_silent() {
+        let [(mut session, _), _other] = connected_sessions();
+        session.poll().unwrap();
+        session.last_received = Instant::now().checked_sub(PEER_TIMEOUT).unwrap();
+        let err = session.poll().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+}
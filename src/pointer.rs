@@ -0,0 +1,70 @@
+//! Aiming with the mouse cursor or a finger, thrust and fire on buttons.
+//!
+//! The mouse shoots with the left button and thrusts with the right one. On a touch screen
+//! two buttons are drawn in the bottom corners and any other touch is the aim point.
+
+use crate::render::Letterbox;
+use asteroids::{cfg, entity::Action};
+use macroquad::{input, math};
+
+pub const TOUCH_BUTTON_RADIUS: f32 = 18.0;
+
+/// The on-screen buttons in arena coordinates, thrust on the left and fire on the right.
+pub fn touch_buttons() -> [(math::Vec2, Action); 2] {
+    let y = cfg::ARENA_HEIGHT - TOUCH_BUTTON_RADIUS - 6.0;
+    [
+        (math::vec2(TOUCH_BUTTON_RADIUS + 6.0, y), Action::Accelerate),
+        (math::vec2(cfg::ARENA_WIDTH - TOUCH_BUTTON_RADIUS - 6.0, y), Action::Shoot),
+    ]
+}
+
+/// What the pointer asks for this frame.
+#[derive(Default)]
+pub struct PointerInput {
+    /// Where the ship should point at, in arena coordinates.
+    pub aim: Option<math::Vec2>,
+    pub held: Vec<Action>,
+}
+
+#[derive(Default)]
+pub struct Pointer {
+    /// Set by the first touch, from then on the mouse is left alone and the buttons shown.
+    pub is_touch: bool,
+}
+
+impl Pointer {
+    pub fn poll(&mut self, letterbox: &Letterbox) -> PointerInput {
+        let mut pointer = PointerInput::default();
+        let touches = input::touches();
+        if !touches.is_empty() && !self.is_touch {
+            self.is_touch = true;
+            // a touch would also click, firing wherever the finger lands
+            input::simulate_mouse_with_touch(false);
+        }
+        if self.is_touch {
+            for touch in touches {
+                if let input::TouchPhase::Ended | input::TouchPhase::Cancelled = touch.phase {
+                    continue;
+                }
+                let position = letterbox.window_to_arena(touch.position);
+                let button = touch_buttons()
+                    .into_iter()
+                    .find(|(center, _)| center.distance(position) <= TOUCH_BUTTON_RADIUS);
+                match button {
+                    Some((_, action)) => pointer.held.push(action),
+                    None => pointer.aim = Some(position),
+                }
+            }
+        } else {
+            let (x, y) = input::mouse_position();
+            pointer.aim = Some(letterbox.window_to_arena(math::vec2(x, y)));
+            if input::is_mouse_button_down(input::MouseButton::Left) {
+                pointer.held.push(Action::Shoot);
+            }
+            if input::is_mouse_button_down(input::MouseButton::Right) {
+                pointer.held.push(Action::Accelerate);
+            }
+        }
+        pointer
+    }
+}
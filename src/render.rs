@@ -0,0 +1,240 @@
+//! Window-side state that the headless simulation does not know about.
+
+use crate::{cfg, palette};
+use asteroids::entity::cmpt::ExplosionKind;
+use macroquad::{color, material, math, rand, texture, window};
+use macroquad_particles as particles;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+pub struct StarBackground {
+    pub static_emitter: particles::Emitter,
+    pub side_emitter: particles::Emitter,
+    pub side_emitter_pos: math::Vec2,
+}
+
+impl Default for StarBackground {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StarBackground {
+    pub fn new() -> Self {
+        let static_cfg = stars();
+        let mut side_cfg = stars();
+        let side_emitter_pos;
+        side_cfg.lifetime_randomness = 0.0;
+        side_cfg.explosiveness = 0.0;
+        side_cfg.size_curve = None;
+        side_cfg.amount = 100;
+        // from left, from right, from top:
+        match rand::gen_range::<i32>(0, 3) {
+            multiplier @ (0 | 1) => {
+                side_cfg.initial_direction =
+                    math::vec2(1.0 - 2.0 * multiplier as f32, rand::gen_range(-0.3, 0.3));
+                side_cfg.emission_shape = particles::EmissionShape::Rect {
+                    width: 0.0,
+                    height: cfg::ARENA_HEIGHT * 1.2,
+                };
+                side_emitter_pos =
+                    math::vec2(cfg::ARENA_WIDTH * multiplier as f32, cfg::ARENA_HEIGHT / 2.0);
+            }
+            2 => {
+                side_cfg.initial_direction = math::vec2(rand::gen_range(-0.3, 0.3), 1.0);
+                side_cfg.emission_shape = particles::EmissionShape::Rect {
+                    width: cfg::ARENA_WIDTH * 1.2,
+                    height: 0.0,
+                };
+                side_emitter_pos = math::vec2(cfg::ARENA_WIDTH / 2.0, 0.0);
+            }
+            _ => unreachable!(),
+        }
+        StarBackground {
+            static_emitter: particles::Emitter::new(static_cfg),
+            side_emitter: particles::Emitter::new(side_cfg),
+            side_emitter_pos,
+        }
+    }
+}
+
+pub struct Renderer {
+    pub show_debug_info: bool,
+    pub canvas: macroquad_canvas::Canvas2D,
+    pub crt_effect: Option<material::Material>,
+    pub explosion_emitters: HashMap<u32, particles::Emitter>,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        let mut canvas = macroquad_canvas::Canvas2D::new(cfg::ARENA_WIDTH, cfg::ARENA_HEIGHT);
+        canvas
+            .get_texture_mut()
+            .set_filter(texture::FilterMode::Nearest);
+        Renderer {
+            show_debug_info: false,
+            canvas,
+            crt_effect: None,
+            explosion_emitters: HashMap::new(),
+        }
+    }
+}
+
+/// Where the arena canvas lands in the window: scaled by a whole number and centered, so
+/// pixels stay square.
+pub struct Letterbox {
+    pub offset: math::Vec2,
+    pub scale: f32,
+}
+
+impl Letterbox {
+    pub fn of_window() -> Self {
+        Letterbox::fit(math::vec2(window::screen_width(), window::screen_height()))
+    }
+
+    /// The largest whole scale of the arena fitting the window, centered in it.
+    fn fit(window_size: math::Vec2) -> Self {
+        let scale = f32::min(
+            (window_size.x / cfg::ARENA_WIDTH).trunc(),
+            (window_size.y / cfg::ARENA_HEIGHT).trunc(),
+        )
+        // a window smaller than the arena shows it cropped
+        .max(1.0);
+        let d_size = window_size - Letterbox::arena_size() * scale;
+        let offset = math::vec2((d_size.x as i32 / 2) as f32, (d_size.y as i32 / 2) as f32);
+        Letterbox { offset, scale }
+    }
+
+    pub fn canvas_size(&self) -> math::Vec2 {
+        Letterbox::arena_size() * self.scale
+    }
+
+    pub fn window_to_arena(&self, position: math::Vec2) -> math::Vec2 {
+        (position - self.offset) / self.scale
+    }
+
+    fn arena_size() -> math::Vec2 {
+        math::vec2(cfg::ARENA_WIDTH, cfg::ARENA_HEIGHT)
+    }
+}
+
+pub fn explosion_emitter(kind: ExplosionKind) -> particles::Emitter {
+    particles::Emitter::new(match kind {
+        ExplosionKind::Ship => ship_explosion(cfg::SHIP_EXPLOSION_COLOR),
+        ExplosionKind::Alien => ship_explosion(cfg::ALIEN_EXPLOSION_COLOR),
+        ExplosionKind::Asteroid => asteroid_explosion(),
+    })
+}
+
+fn stars() -> particles::EmitterConfig {
+    particles::EmitterConfig {
+        lifetime: 70.0,
+        lifetime_randomness: 0.4,
+        amount: 50,
+        explosiveness: 0.2,
+        local_coords: true,
+        initial_direction: math::vec2(0.001, 0.0),
+        initial_velocity: 28.0,
+        initial_velocity_randomness: 0.8,
+        size: 1.2,
+        size_randomness: 0.6,
+        size_curve: Some(particles::Curve {
+            points: vec![(0.0, 0.0), (0.05, 1.0), (0.85, 0.8), (1.0, 0.0)],
+            ..Default::default()
+        }),
+        emission_shape: particles::EmissionShape::Rect {
+            width: cfg::ARENA_WIDTH,
+            height: cfg::ARENA_HEIGHT,
+        },
+        shape: particles::ParticleShape::Circle { subdivisions: 4 },
+        colors_curve: particles::ColorCurve {
+            start: palette::WHITE,
+            mid: palette::WHITE,
+            end: palette::WHITE,
+        },
+        ..Default::default()
+    }
+}
+
+fn asteroid_explosion() -> particles::EmitterConfig {
+    particles::EmitterConfig {
+        one_shot: true,
+        lifetime: cfg::ASTEROID_EXPLOSION_LIFETIME,
+        explosiveness: 1.0,
+        amount: 8,
+        local_coords: true,
+        initial_direction: math::vec2(0.0, 1.0),
+        initial_direction_spread: 2.0 * PI,
+        initial_velocity: 60.0,
+        initial_velocity_randomness: 0.4,
+        size: 1.7,
+        size_curve: Some(particles::Curve {
+            points: vec![(0.0, 1.0), (0.85, 1.0), (1.0, 0.0)],
+            ..Default::default()
+        }),
+        shape: particles::ParticleShape::Circle { subdivisions: 7 },
+        colors_curve: particles::ColorCurve {
+            start: palette::LIGHTGRAY,
+            mid: palette::LIGHTGRAY,
+            end: palette::LIGHTGRAY,
+        },
+        ..Default::default()
+    }
+}
+
+fn ship_explosion(color: color::Color) -> particles::EmitterConfig {
+    particles::EmitterConfig {
+        one_shot: true,
+        lifetime: cfg::SHIP_EXPLOSION_LIFETIME,
+        explosiveness: 1.0,
+        amount: 8,
+        local_coords: true,
+        initial_direction: math::vec2(0.0, 1.0),
+        initial_direction_spread: 2.0 * PI,
+        initial_velocity: 20.0,
+        initial_velocity_randomness: 0.4,
+        size: 2.0,
+        size_curve: Some(particles::Curve {
+            points: vec![(0.0, 1.0), (0.85, 1.0), (1.0, 0.0)],
+            ..Default::default()
+        }),
+        shape: particles::ParticleShape::Circle { subdivisions: 4 },
+        colors_curve: particles::ColorCurve {
+            start: color,
+            mid: color,
+            end: color,
+        },
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arena_is_centered_at_a_whole_scale() {
+        let letterbox = Letterbox::fit(math::vec2(1400.0, 800.0));
+        assert_eq!(letterbox.scale, 3.0);
+        assert_eq!(letterbox.offset, math::vec2(52.0, 40.0));
+        assert_eq!(letterbox.canvas_size(), math::vec2(1296.0, 720.0));
+    }
+
+    #[test]
+    fn window_corners_of_the_canvas_map_to_arena_corners() {
+        let letterbox = Letterbox::fit(math::vec2(1400.0, 800.0));
+        assert_eq!(letterbox.window_to_arena(letterbox.offset), math::Vec2::ZERO);
+        let far_corner = letterbox.offset + letterbox.canvas_size();
+        assert_eq!(
+            letterbox.window_to_arena(far_corner),
+            math::vec2(cfg::ARENA_WIDTH, cfg::ARENA_HEIGHT)
+        );
+    }
+
+    #[test]
+    fn small_window_shows_the_arena_cropped() {
+        let letterbox = Letterbox::fit(math::vec2(300.0, 200.0));
+        assert_eq!(letterbox.scale, 1.0);
+        assert_eq!(letterbox.window_to_arena(math::Vec2::ZERO), math::vec2(66.0, 20.0));
+    }
+}
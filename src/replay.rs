@@ -0,0 +1,274 @@
+//! Replay files: the seed, mode and config of a [`crate::Game`] followed by the actions of
+//! every step.
+//!
+//! Layout: `ASTR` magic, a version byte, the seed as little-endian `u64`, a byte of
+//! [`GameMode`] flags, the [`Config`] as TOML prefixed with its little-endian `u32` length,
+//! then the steps. A step holds an input for each player of the mode, in player order.
+//! An input is a byte with bit `action as u8` set for each held [`Action`], a byte of
+//! [`Steering`] flags, and the steering's thrust and heading as little-endian `f32`s when
+//! the flags say they are there.
+//!
+//! The version goes up whenever the layout changes or the same inputs play out differently,
+//! so a replay is only read by the version of the game that recorded it.
+
+use crate::config::Config;
+use crate::entity::{Action, GameMode, PlayerInput, Steering};
+use std::collections::HashSet;
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 4] = b"ASTR";
+const VERSION: u8 = 9;
+const BOUNCING_ASTEROIDS: u8 = 1;
+const CO_OP: u8 = 2;
+const FRIENDLY_FIRE: u8 = 4;
+const VERSUS: u8 = 8;
+const HAS_STEERING: u8 = 1;
+const HAS_HEADING: u8 = 2;
+
+pub struct Replay {
+    pub seed: u64,
+    pub mode: GameMode,
+    pub config: Config,
+    pub steps: Vec<Vec<PlayerInput>>,
+}
+
+impl Replay {
+    pub fn read(mut reader: impl Read) -> io::Result<Self> {
+        let (seed, mode, config) = read_header(&mut reader)?;
+        let player_count = mode.player_count();
+        let mut steps = Vec::new();
+        let mut actions = [0];
+        while reader.read(&mut actions)? != 0 {
+            let mut step = Vec::with_capacity(player_count);
+            for i in 0..player_count {
+                if i > 0 {
+                    reader.read_exact(&mut actions)?;
+                }
+                step.push(read_input(actions[0], &mut reader)?);
+            }
+            steps.push(step);
+        }
+        Ok(Replay { seed, mode, config, steps })
+    }
+}
+
+/// Writes steps as they happen, so the file is usable even if the game is killed.
+pub struct Recorder<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut writer: W, seed: u64, mode: GameMode, config: &Config) -> io::Result<Self> {
+        write_header(&mut writer, seed, mode, config)?;
+        Ok(Recorder { writer })
+    }
+
+    /// Takes an input for every player of the mode the recording was started with.
+    pub fn record(&mut self, inputs: &[PlayerInput]) -> io::Result<()> {
+        for input in inputs {
+            write_input(&mut self.writer, input)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Everything before the steps, also what a netplay host sends to the player joining it.
+pub(crate) fn write_header(
+    mut writer: impl Write,
+    seed: u64,
+    mode: GameMode,
+    config: &Config,
+) -> io::Result<()> {
+    let config = config.to_toml();
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION])?;
+    writer.write_all(&seed.to_le_bytes())?;
+    writer.write_all(&[encode_mode(mode)])?;
+    writer.write_all(&(config.len() as u32).to_le_bytes())?;
+    writer.write_all(config.as_bytes())
+}
+
+/// Returns the seed, mode and config.
+pub(crate) fn read_header(mut reader: impl Read) -> io::Result<(u64, GameMode, Config)> {
+    let mut header = [0; 14];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(invalid_data("not a replay file"));
+    }
+    let version = header[4];
+    if version != VERSION {
+        return Err(invalid_data(format!(
+            "made by another version of the game, version {} instead of {}",
+            version, VERSION
+        )));
+    }
+    let seed = u64::from_le_bytes(header[5..13].try_into().unwrap());
+    let mode = decode_mode(header[13])?;
+    let mut length = [0; 4];
+    reader.read_exact(&mut length)?;
+    // the length is untrusted, so the text grows as it is read instead of up front
+    let length = u32::from_le_bytes(length) as usize;
+    let mut text = String::new();
+    reader
+        .by_ref()
+        .take(length as u64)
+        .read_to_string(&mut text)?;
+    if text.len() != length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let config = Config::from_toml(&text).map_err(invalid_data)?;
+    Ok((seed, mode, config))
+}
+
+pub(crate) fn write_input(mut writer: impl Write, input: &PlayerInput) -> io::Result<()> {
+    writer.write_all(&[encode(&input.actions)])?;
+    write_steering(writer, input.steering)
+}
+
+/// Reads the rest of an input after its action byte, which tells the end of a replay apart.
+pub(crate) fn read_input(actions: u8, reader: impl Read) -> io::Result<PlayerInput> {
+    let steering = read_steering(reader)?;
+    Ok(PlayerInput { actions: decode(actions)?, steering })
+}
+
+fn encode_mode(mode: GameMode) -> u8 {
+    [
+        (mode.bouncing_asteroids, BOUNCING_ASTEROIDS),
+        (mode.co_op, CO_OP),
+        (mode.friendly_fire, FRIENDLY_FIRE),
+        (mode.versus, VERSUS),
+    ]
+    .iter()
+    .filter(|&&(is_on, _)| is_on)
+    .fold(0, |flags, &(_, flag)| flags | flag)
+}
+
+fn decode_mode(flags: u8) -> io::Result<GameMode> {
+    if flags & !(BOUNCING_ASTEROIDS | CO_OP | FRIENDLY_FIRE | VERSUS) != 0 {
+        return Err(invalid_data(format!("unknown game mode flags: {:#010b}", flags)));
+    }
+    Ok(GameMode {
+        bouncing_asteroids: flags & BOUNCING_ASTEROIDS != 0,
+        co_op: flags & CO_OP != 0,
+        friendly_fire: flags & FRIENDLY_FIRE != 0,
+        versus: flags & VERSUS != 0,
+    })
+}
+
+fn write_steering(mut writer: impl Write, steering: Option<Steering>) -> io::Result<()> {
+    let steering = match steering {
+        Some(steering) => steering,
+        None => return writer.write_all(&[0]),
+    };
+    let heading_flag = steering.heading.map_or(0, |_| HAS_HEADING);
+    writer.write_all(&[HAS_STEERING | heading_flag])?;
+    writer.write_all(&steering.thrust.to_le_bytes())?;
+    if let Some(heading) = steering.heading {
+        writer.write_all(&heading.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_steering(mut reader: impl Read) -> io::Result<Option<Steering>> {
+    let mut flags = [0];
+    reader.read_exact(&mut flags)?;
+    let flags = flags[0];
+    if flags & !(HAS_STEERING | HAS_HEADING) != 0 || flags == HAS_HEADING {
+        return Err(invalid_data(format!("unknown steering flags: {:#010b}", flags)));
+    }
+    if flags & HAS_STEERING == 0 {
+        return Ok(None);
+    }
+    let thrust = read_f32(&mut reader)?;
+    let heading = if flags & HAS_HEADING != 0 {
+        Some(read_f32(&mut reader)?)
+    } else {
+        None
+    };
+    Ok(Some(Steering { heading, thrust }))
+}
+
+fn read_f32(mut reader: impl Read) -> io::Result<f32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+fn encode(actions: &HashSet<Action>) -> u8 {
+    actions
+        .iter()
+        .fold(0, |bits, &action| bits | 1 << action as u8)
+}
+
+fn decode(bits: u8) -> io::Result<HashSet<Action>> {
+    if u32::from(bits) >> Action::ALL.len() != 0 {
+        return Err(invalid_data(format!("unknown actions in step: {:#010b}", bits)));
+    }
+    Ok(Action::ALL
+        .iter()
+        .copied()
+        .filter(|&action| bits & 1 << action as u8 != 0)
+        .collect())
+}
+
+pub(crate) fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(actions: &[Action], steering: Option<Steering>) -> PlayerInput {
+        PlayerInput {
+            actions: actions.iter().copied().collect(),
+            steering,
+        }
+    }
+
+    #[test]
+    fn co_op_steps_survive_roundtrip_with_and_without_steering() {
+        let mode = GameMode { co_op: true, ..Default::default() };
+        let steps = vec![
+            vec![input(&[Action::Shoot], None), input(&[], None)],
+            vec![
+                input(&[], Some(Steering { heading: None, thrust: 0.5 })),
+                input(&[Action::Shield], Some(Steering { heading: Some(-1.0), thrust: 0.0 })),
+            ],
+        ];
+        let mut recorder = Recorder::new(Vec::new(), 7, mode, &Config::default()).unwrap();
+        for step in &steps {
+            recorder.record(step).unwrap();
+        }
+        let replay = Replay::read(recorder.writer.as_slice()).unwrap();
+        assert_eq!((replay.seed, replay.mode), (7, mode));
+        assert_eq!(replay.steps, steps);
+    }
+
+    #[test]
+    fn replays_of_other_versions_are_rejected() {
+        let mut bytes = Vec::new();
+        write_header(&mut bytes, 7, GameMode::default(), &Config::default()).unwrap();
+        for version in [1, VERSION - 1, VERSION + 1] {
+            bytes[4] = version;
+            let err = Replay::read(bytes.as_slice()).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn config_longer_than_the_file_is_rejected() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&7u64.to_le_bytes());
+        bytes.push(0);
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(b"[ship]\n");
+        let err = Replay::read(bytes.as_slice()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
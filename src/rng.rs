@@ -0,0 +1,86 @@
+//! Seedable random generator owned by a [`crate::Game`].
+//!
+//! Same PCG as `macroquad::rand`, but without the global state, so two games started
+//! with the same seed and fed the same actions stay identical.
+
+const DEFAULT_INC: u64 = 1442695040888963407;
+const MULTIPLIER: u64 = 6364136223846793005;
+
+#[derive(Clone, Hash)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Rng { state: 0 };
+        rng.rand();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.rand();
+        rng
+    }
+
+    /// Returns a pseudo-random number in the range of 0 to `u32::MAX`.
+    pub fn rand(&mut self) -> u32 {
+        let old_state = self.state;
+        self.state = old_state.wrapping_mul(MULTIPLIER).wrapping_add(DEFAULT_INC);
+        let xor_shifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rot = (old_state >> 59) as u32;
+        xor_shifted.rotate_right(rot)
+    }
+
+    pub fn gen_range<T: RandomRange>(&mut self, low: T, high: T) -> T {
+        T::gen_range(self, low, high)
+    }
+}
+
+pub trait RandomRange {
+    fn gen_range(rng: &mut Rng, low: Self, high: Self) -> Self;
+}
+
+impl RandomRange for f32 {
+    fn gen_range(rng: &mut Rng, low: Self, high: Self) -> Self {
+        let r = rng.rand() as f32 / u32::MAX as f32;
+        low + (high - low) * r
+    }
+}
+
+macro_rules! impl_random_range {
+    ($($t:ty),*) => {$(
+        impl RandomRange for $t {
+            /// Returns a number from `low` up to but not including `high`.
+            fn gen_range(rng: &mut Rng, low: Self, high: Self) -> Self {
+                // scaled in integers, since a float near 1.0 would round up to `high`
+                let span = (high as i64 - low as i64) as u64;
+                (low as i64 + ((rng.rand() as u64 * span) >> 32) as i64) as $t
+            }
+        }
+    )*};
+}
+
+impl_random_range!(i32, u32, usize);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A generator whose next `rand()` is `u32::MAX`.
+    fn rng_at_max() -> Rng {
+        let rng = Rng { state: 0x07ff_fe00_0000_0000 };
+        assert_eq!(rng.clone().rand(), u32::MAX);
+        rng
+    }
+
+    #[test]
+    fn integer_range_excludes_high() {
+        assert_eq!(rng_at_max().gen_range(0, 2), 1);
+        assert_eq!(rng_at_max().gen_range(-3i32, 3), 2);
+        assert_eq!(rng_at_max().gen_range(5usize, 6), 5);
+    }
+}
@@ -0,0 +1,130 @@
+//! Player preferences kept between runs, unlike the tuning values of a config file.
+
+use asteroids::{
+    entity::Action,
+    keymap::{self, Keymap},
+};
+use macroquad::input::KeyCode;
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+pub const SETTINGS_FILE_NAME: &str = "settings.toml";
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub control_scheme: ControlScheme,
+    pub keymap: Keymap,
+    /// Used in co-op only.
+    #[serde(deserialize_with = "keymap::deserialize_second_player")]
+    pub second_player_keymap: Keymap,
+    pub gamepad: GamepadSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            control_scheme: Default::default(),
+            keymap: Default::default(),
+            second_player_keymap: Keymap::second_player(),
+            gamepad: Default::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlScheme {
+    /// The keyboard and gamepads.
+    #[default]
+    Keys,
+    /// The ship turns towards the mouse cursor or touch point, unless a turn key is held.
+    Pointer,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GamepadSettings {
+    /// How far the stick and the thrust trigger move before they count, from 0 to 1.
+    pub deadzone: f32,
+    /// The stick points the ship and the trigger thrusts by how hard it's pulled; without
+    /// it they work like the turn and accelerate keys.
+    pub analog_steering: bool,
+}
+
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        GamepadSettings {
+            deadzone: 0.2,
+            analog_steering: true,
+        }
+    }
+}
+
+impl Settings {
+    /// A missing file gives the defaults, it's written once something is changed.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Default::default()),
+            Err(err) => return Err(format!("can't read settings {}: {}", path.display(), err)),
+        };
+        let settings: Settings = toml::from_str(&text)
+            .map_err(|err| format!("bad settings {}: {}", path.display(), err))?;
+        let [keymap, second_keymap] = settings.keymaps();
+        let is_bound_twice =
+            |&(key, _): &(KeyCode, Action)| second_keymap.bindings().any(|(k, _)| k == key);
+        if let Some((key, _)) = keymap.bindings().find(is_bound_twice) {
+            return Err(format!(
+                "bad settings {}: key `{:?}` is bound for both players",
+                path.display(),
+                key
+            ));
+        }
+        let deadzone = settings.gamepad.deadzone;
+        if !(0.0..1.0).contains(&deadzone) {
+            return Err(format!(
+                "bad settings {}: invalid value for key `gamepad.deadzone`: \
+                 must be at least 0 and below 1, got {}",
+                path.display(),
+                deadzone
+            ));
+        }
+        Ok(settings)
+    }
+
+    pub fn keymaps(&self) -> [&Keymap; 2] {
+        [&self.keymap, &self.second_player_keymap]
+    }
+
+    pub fn keymap_mut(&mut self, player_id: usize) -> &mut Keymap {
+        match player_id {
+            0 => &mut self.keymap,
+            _ => &mut self.second_player_keymap,
+        }
+    }
+
+    /// Binds the key for the player, taking it away from the other player too.
+    pub fn bind(&mut self, player_id: usize, action: Action, key: KeyCode) -> Result<(), String> {
+        self.keymap_mut(1 - player_id).unbind(key)?;
+        self.keymap_mut(player_id).bind(action, key)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = toml::to_string(self).expect("settings are always representable in TOML");
+        fs::write(path, text)
+            .map_err(|err| format!("can't save settings {}: {}", path.display(), err))
+    }
+}
+
+/// The screen listing every action with its keys, opened from the pause.
+#[derive(Default)]
+pub struct ControlsScreen {
+    /// Whose keys are shown.
+    pub player_id: usize,
+    /// Index into [`asteroids::entity::Action::ALL`].
+    pub selected: usize,
+    pub is_waiting_for_key: bool,
+    /// Why the last change was refused.
+    pub error: Option<String>,
+}
@@ -1,5 +1,5 @@
 use crate::{cfg, entity};
-use macroquad::rand;
+use macroquad::math;
 use std::f32::consts::PI;
 
 pub fn update(game: &mut crate::Game, _dt: f32) {
@@ -7,22 +7,36 @@ pub fn update(game: &mut crate::Game, _dt: f32) {
         let time_to_shift = alien.shift_timer == 0.0;
         let time_to_shoot = alien.weapon_cooldown_timer == 0.0;
         if time_to_shift {
-            alien.shift_timer = cfg::ALIEN_SHIFT_PERIOD;
+            alien.shift_timer = game.config.aliens.shift_period;
             let d_angle = PI / 4.0;
             let origin_angle = PI * alien.direction as u32 as f32;
-            alien.body.angle += d_angle * rand::gen_range(-2_i32, 2) as f32;
-            alien.body.angle = alien
-                .body
-                .angle
-                .clamp(origin_angle - d_angle, origin_angle + d_angle);
+            // measured from the origin direction, so flying left doesn't wrap around ±PI
+            let course = math::Mat2::from_angle(-origin_angle).mul_vec2(alien.body.velocity);
+            let angle = (course.y.atan2(course.x) + d_angle * game.rng.gen_range(-2_i32, 2) as f32)
+                .clamp(-d_angle, d_angle)
+                + origin_angle;
+            alien.body.velocity = math::vec2(angle.cos(), angle.sin()) * course.length();
         }
-        if time_to_shoot && game.ship.is_some() {
-            alien.weapon_cooldown_timer = cfg::ALIEN_SHOOT_PERIOD;
-            let ship = game.ship.as_ref().unwrap();
+        // the nearest ship in sight
+        let target = game
+            .players
+            .iter()
+            .filter_map(|p| p.ship.as_ref())
+            .filter(|sh| !sh.is_in_hyperspace)
+            .min_by(|a, b| {
+                let distance = |sh: &&entity::Ship| sh.position.distance(alien.position);
+                distance(a).total_cmp(&distance(b))
+            });
+        if let Some(ship) = target.filter(|_| time_to_shoot) {
+            alien.weapon_cooldown_timer = game.config.aliens.shoot_period;
             let shoot_angle =
                 f32::atan2(ship.position.y - alien.position.y, ship.position.x - alien.position.x);
-            game.bullets
-                .push(entity::Bullet::new(alien.position, shoot_angle, Some(alien.kind)));
+            game.bullets.push(entity::Bullet::new(
+                alien.position,
+                shoot_angle,
+                entity::Owner::Alien(alien.kind),
+                &game.config,
+            ));
         }
         if alien.position.x < -4.0 || cfg::ARENA_WIDTH + 4.0 < alien.position.x {
             alien.is_destroyed = true;
@@ -1,5 +1,7 @@
 pub fn update(game: &mut crate::Game, _dt: f32) {
-    game.ship = game.ship.take().filter(|sh| !sh.is_destroyed);
+    for player in &mut game.players {
+        player.ship = player.ship.take().filter(|sh| !sh.is_destroyed);
+    }
     game.aliens.retain(|a| !a.is_destroyed);
     game.bullets.retain(|b| b.life_timer > 0.0);
     game.asteroids.retain(|a| !a.is_destroyed);
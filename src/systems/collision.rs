@@ -1,66 +1,367 @@
-use crate::entity::cmpt;
+use crate::{arena, cfg, entity, entity::cmpt};
 use macroquad::math;
 
 pub fn update(game: &mut crate::Game, _dt: f32) {
-    let (mut enemy_bullets, mut ship_bullets): (Vec<_>, _) =
-        game.bullets.iter_mut().partition(|b| b.from_enemy);
+    let cell_size = cell_size(game);
+    let polygons = game.config.collisions.polygons;
+    let (mut enemy_bullets, mut ship_bullets): (Vec<_>, _) = game
+        .bullets
+        .iter_mut()
+        .partition(|b| matches!(b.owner, entity::Owner::Alien(_)));
+    let mut vulnerable_ships: Vec<_> = game
+        .players
+        .iter_mut()
+        .filter_map(|p| Some((p.id, p.ship.as_mut()?)))
+        .filter(|(_, sh)| sh.invulnerability_timer == 0.0 && !sh.is_in_hyperspace)
+        .collect();
+    let mut asteroid_grid = arena::Grid::new(cell_size);
+    for (i, asteroid) in game.asteroids.iter().enumerate() {
+        asteroid_grid.insert(i, asteroid.position);
+    }
+    let mut enemy_bullet_grid = arena::Grid::new(cell_size);
+    for (i, enemy_bullet) in enemy_bullets.iter().enumerate() {
+        enemy_bullet_grid.insert(i, enemy_bullet.position);
+    }
     for alien in &mut game.aliens {
-        for ship in &mut game.ship {
-            do_collision(alien.position, &mut alien.body, ship.position, &mut ship.body);
+        for (_, ship) in &mut vulnerable_ships {
+            do_sprite_collision(
+                alien.position,
+                &alien.sprite,
+                &mut alien.body,
+                ship.position,
+                &ship.sprite,
+                &mut ship.body,
+                polygons,
+            );
         }
     }
     for enemy_bullet in &mut enemy_bullets {
-        for ship in &mut game.ship {
-            do_collision(
-                enemy_bullet.position,
-                &mut enemy_bullet.body,
+        for (_, ship) in &mut vulnerable_ships {
+            do_bullet_collision(
+                enemy_bullet,
+                ship.prev_position,
                 ship.position,
+                &ship.sprite,
                 &mut ship.body,
+                polygons,
             );
         }
-        for asteroid in &mut game.asteroids {
-            do_collision(
-                enemy_bullet.position,
-                &mut enemy_bullet.body,
+        for i in asteroid_grid.near(enemy_bullet.position) {
+            let asteroid = &mut game.asteroids[i];
+            do_bullet_collision(
+                enemy_bullet,
+                asteroid.prev_position,
                 asteroid.position,
+                &asteroid.sprite,
                 &mut asteroid.body,
+                polygons,
             );
         }
     }
     for ship_bullet in &mut ship_bullets {
-        for enemy_bullet in &mut enemy_bullets {
-            do_collision(
-                ship_bullet.position,
-                &mut ship_bullet.body,
+        let owner = match ship_bullet.owner {
+            entity::Owner::Player(id) => id,
+            entity::Owner::Alien(_) => unreachable!(),
+        };
+        if game.mode.is_friendly_fire() {
+            for (_, ship) in vulnerable_ships.iter_mut().filter(|(id, _)| *id != owner) {
+                if do_bullet_collision(
+                    ship_bullet,
+                    ship.prev_position,
+                    ship.position,
+                    &ship.sprite,
+                    &mut ship.body,
+                    polygons,
+                ) {
+                    ship.body.hit_by_player = Some(owner);
+                }
+            }
+        }
+        for i in enemy_bullet_grid.near(ship_bullet.position) {
+            let enemy_bullet = &mut enemy_bullets[i];
+            do_bullet_collision(
+                ship_bullet,
+                enemy_bullet.prev_position,
                 enemy_bullet.position,
+                &enemy_bullet.sprite,
                 &mut enemy_bullet.body,
+                polygons,
             );
         }
         for alien in &mut game.aliens {
-            do_collision(
-                ship_bullet.position,
-                &mut ship_bullet.body,
+            if do_bullet_collision(
+                ship_bullet,
+                alien.prev_position,
                 alien.position,
+                &alien.sprite,
                 &mut alien.body,
-            );
+                polygons,
+            ) {
+                alien.body.hit_by_player = Some(owner);
+            }
         }
-        for asteroid in &mut game.asteroids {
-            do_collision(
-                ship_bullet.position,
-                &mut ship_bullet.body,
+        for i in asteroid_grid.near(ship_bullet.position) {
+            let asteroid = &mut game.asteroids[i];
+            if do_bullet_collision(
+                ship_bullet,
+                asteroid.prev_position,
                 asteroid.position,
+                &asteroid.sprite,
                 &mut asteroid.body,
-            );
+                polygons,
+            ) {
+                asteroid.body.hit_by_player = Some(owner);
+            }
         }
     }
     for asteroid in &mut game.asteroids {
-        for ship in &mut game.ship {
-            do_collision(asteroid.position, &mut asteroid.body, ship.position, &mut ship.body);
+        for (_, ship) in &mut vulnerable_ships {
+            do_sprite_collision(
+                asteroid.position,
+                &asteroid.sprite,
+                &mut asteroid.body,
+                ship.position,
+                &ship.sprite,
+                &mut ship.body,
+                polygons,
+            );
         }
         for alien in &mut game.aliens {
-            do_collision(asteroid.position, &mut asteroid.body, alien.position, &mut alien.body);
+            do_sprite_collision(
+                asteroid.position,
+                &asteroid.sprite,
+                &mut asteroid.body,
+                alien.position,
+                &alien.sprite,
+                &mut alien.body,
+                polygons,
+            );
+        }
+    }
+    if game.mode.bouncing_asteroids {
+        bounce_asteroids(&mut game.asteroids, &asteroid_grid);
+    }
+}
+
+/// Cell size of the grids bodies are sorted into for the game as it is.
+pub fn cell_size(game: &crate::Game) -> f32 {
+    // only bullets are looked up in the grids, and nothing they can hit is bigger than
+    // the biggest asteroid, so colliding pairs are always in neighbouring cells
+    let mut max_asteroid_radius = game
+        .config
+        .asteroids
+        .stages
+        .iter()
+        .fold(0.0, |radius, stage| f32::max(radius, stage.radius));
+    let mut bullet_radius = cfg::BULLET_RADIUS;
+    if game.config.collisions.polygons {
+        max_asteroid_radius = game
+            .asteroids
+            .iter()
+            .fold(max_asteroid_radius, |radius, a| f32::max(radius, a.sprite.reach()));
+        bullet_radius = f32::max(bullet_radius, cfg::BULLET_LENGTH);
+    }
+    // bullets are tested along the way they came, relative to what they may hit
+    let max_travel = game
+        .bullets
+        .iter()
+        .map(|b| (b.prev_position, b.position))
+        .chain(game.asteroids.iter().map(|a| (a.prev_position, a.position)))
+        .fold(0.0, |travel, (prev_pos, pos)| {
+            f32::max(travel, arena::wrapped_delta(prev_pos, pos).length())
+        });
+    let cell_size = bullet_radius + max_asteroid_radius + 2.0 * max_travel;
+    if game.mode.bouncing_asteroids {
+        f32::max(cell_size, 2.0 * max_asteroid_radius)
+    } else {
+        cell_size
+    }
+}
+
+/// Elastic collisions between asteroid hit circles, in the bouncing asteroids game mode.
+fn bounce_asteroids(asteroids: &mut [entity::Asteroid], grid: &arena::Grid) {
+    for i in 0..asteroids.len() {
+        for j in grid.near(asteroids[i].position).filter(|&j| j > i) {
+            let (left, right) = asteroids.split_at_mut(j);
+            bounce(&mut left[i].body, left[i].position, &mut right[0].body, right[0].position);
+        }
+    }
+}
+
+fn bounce(a_body: &mut cmpt::Body, a_pos: math::Vec2, b_body: &mut cmpt::Body, b_pos: math::Vec2) {
+    let d_pos = arena::wrapped_delta(a_pos, b_pos);
+    let distance = d_pos.length();
+    if distance == 0.0 || distance > a_body.radius + b_body.radius {
+        return;
+    }
+    let normal = d_pos / distance;
+    let approach_speed = (a_body.velocity - b_body.velocity).dot(normal);
+    // already moving apart, like the children of a split, so overlaps sort themselves out
+    if approach_speed <= 0.0 {
+        return;
+    }
+    let (a_mass, b_mass) = (a_body.mass(), b_body.mass());
+    let impulse = 2.0 * approach_speed / (a_mass + b_mass);
+    a_body.velocity -= normal * impulse * b_mass;
+    b_body.velocity += normal * impulse * a_mass;
+}
+
+/// Collides the drawn outlines with `polygons` on, hit circles otherwise.
+fn do_sprite_collision(
+    a_pos: math::Vec2,
+    a_sprite: &cmpt::Sprite,
+    a_body: &mut cmpt::Body,
+    b_pos: math::Vec2,
+    b_sprite: &cmpt::Sprite,
+    b_body: &mut cmpt::Body,
+    polygons: bool,
+) -> bool {
+    if !polygons {
+        return do_collision(a_pos, a_body, b_pos, b_body);
+    }
+    let d_pos = arena::wrapped_delta(a_pos, b_pos);
+    let is_intersecting = d_pos.length() <= a_sprite.reach() + b_sprite.reach()
+        && outlines_intersect(&outline(a_sprite, math::Vec2::ZERO), &outline(b_sprite, d_pos));
+    if is_intersecting {
+        a_body.is_hit = true;
+        b_body.is_hit = true;
+    }
+    is_intersecting
+}
+
+/// Tests everything the bullet passed over during the last step, following the motion
+/// of the other body, so a fast bullet can't skip over a small asteroid.
+fn do_bullet_collision(
+    bullet: &mut entity::Bullet,
+    b_prev_pos: math::Vec2,
+    b_pos: math::Vec2,
+    b_sprite: &cmpt::Sprite,
+    b_body: &mut cmpt::Body,
+    polygons: bool,
+) -> bool {
+    let d_pos = arena::wrapped_delta(b_pos, bullet.position);
+    let sweep = arena::wrapped_delta(bullet.prev_position, bullet.position)
+        - arena::wrapped_delta(b_prev_pos, b_pos);
+    let is_intersecting = if polygons {
+        d_pos.length() <= sweep.length() + bullet.sprite.reach() + b_sprite.reach()
+            && outlines_intersect(
+                &swept_bullet_outline(&bullet.sprite, d_pos, sweep),
+                &outline(b_sprite, math::Vec2::ZERO),
+            )
+    } else {
+        distance_to_segment(math::Vec2::ZERO, d_pos - sweep, d_pos)
+            <= bullet.body.radius + b_body.radius
+    };
+    if is_intersecting {
+        bullet.body.is_hit = true;
+        b_body.is_hit = true;
+        b_body.impact_angle = Some(bullet.sprite.angle);
+    }
+    is_intersecting
+}
+
+/// Drawn shape of a sprite in arena space.
+enum Outline {
+    Segment(math::Vec2, math::Vec2),
+    /// Every vector layer, so the ship's nose and wings count too.
+    Polygons(Vec<Vec<math::Vec2>>),
+}
+
+fn outline(sprite: &cmpt::Sprite, offset: math::Vec2) -> Outline {
+    match &sprite.variant {
+        cmpt::SpriteVariant::Bullet { .. } => {
+            let direction = math::vec2(sprite.angle.cos(), sprite.angle.sin());
+            Outline::Segment(offset, offset + direction * cfg::BULLET_LENGTH)
         }
+        cmpt::SpriteVariant::Vector { layers } => {
+            let rotation = math::Mat2::from_angle(sprite.angle);
+            let polygons = layers
+                .iter()
+                .map(|(points, _)| {
+                    points
+                        .iter()
+                        .map(|&p| rotation.mul_vec2(p) + offset)
+                        .collect()
+                })
+                .collect();
+            Outline::Polygons(polygons)
+        }
+    }
+}
+
+/// The area covered by the bullet line moving by `sweep` to `offset`.
+fn swept_bullet_outline(sprite: &cmpt::Sprite, offset: math::Vec2, sweep: math::Vec2) -> Outline {
+    let tip = math::vec2(sprite.angle.cos(), sprite.angle.sin()) * cfg::BULLET_LENGTH;
+    let start = offset - sweep;
+    Outline::Polygons(vec![vec![start, start + tip, offset + tip, offset]])
+}
+
+fn outlines_intersect(a: &Outline, b: &Outline) -> bool {
+    match (a, b) {
+        (Outline::Segment(a_start, a_end), Outline::Segment(b_start, b_end)) => {
+            segments_intersect(*a_start, *a_end, *b_start, *b_end)
+        }
+        (Outline::Segment(start, end), Outline::Polygons(polygons))
+        | (Outline::Polygons(polygons), Outline::Segment(start, end)) => polygons
+            .iter()
+            .any(|polygon| is_inside(*start, polygon) || crosses_edges(*start, *end, polygon)),
+        (Outline::Polygons(a_polygons), Outline::Polygons(b_polygons)) => {
+            a_polygons.iter().any(|a| {
+                b_polygons.iter().any(|b| {
+                    is_inside(a[0], b)
+                        || is_inside(b[0], a)
+                        || edges(a).any(|(start, end)| crosses_edges(start, end, b))
+                })
+            })
+        }
+    }
+}
+
+fn edges(polygon: &[math::Vec2]) -> impl Iterator<Item = (math::Vec2, math::Vec2)> + '_ {
+    polygon
+        .iter()
+        .copied()
+        .zip(polygon.iter().copied().cycle().skip(1))
+}
+
+fn crosses_edges(start: math::Vec2, end: math::Vec2, polygon: &[math::Vec2]) -> bool {
+    edges(polygon)
+        .any(|(edge_start, edge_end)| segments_intersect(start, end, edge_start, edge_end))
+}
+
+/// Even-odd rule, asteroid outlines aren't convex.
+fn is_inside(point: math::Vec2, polygon: &[math::Vec2]) -> bool {
+    edges(polygon)
+        .filter(|&(a, b)| {
+            (a.y > point.y) != (b.y > point.y)
+                && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        })
+        .count()
+        % 2
+        == 1
+}
+
+/// Touching counts as intersecting.
+fn segments_intersect(a1: math::Vec2, a2: math::Vec2, b1: math::Vec2, b2: math::Vec2) -> bool {
+    let side = |from: math::Vec2, to: math::Vec2, p: math::Vec2| (to - from).perp_dot(p - from);
+    let (d1, d2) = (side(b1, b2, a1), side(b1, b2, a2));
+    let (d3, d4) = (side(a1, a2, b1), side(a1, a2, b2));
+    if d1 == 0.0 && d2 == 0.0 {
+        // collinear, so they intersect only if their extents overlap
+        return a1.min(a2).cmple(b1.max(b2)).all() && b1.min(b2).cmple(a1.max(a2)).all();
     }
+    d1 * d2 <= 0.0 && d3 * d4 <= 0.0
+}
+
+fn distance_to_segment(point: math::Vec2, start: math::Vec2, end: math::Vec2) -> f32 {
+    let segment = end - start;
+    let length_squared = segment.length_squared();
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        ((point - start).dot(segment) / length_squared).clamp(0.0, 1.0)
+    };
+    (start + segment * t - point).length()
 }
 
 fn do_collision(
@@ -68,12 +369,241 @@ fn do_collision(
     a_body: &mut cmpt::Body,
     b_pos: math::Vec2,
     b_body: &mut cmpt::Body,
-) {
-    let d_pos = a_pos - b_pos;
+) -> bool {
+    let d_pos = arena::wrapped_delta(a_pos, b_pos);
     let is_intersecting =
         d_pos.x.powi(2) + d_pos.y.powi(2) <= (a_body.radius + b_body.radius).powi(2);
     if is_intersecting {
         a_body.is_hit = true;
         b_body.is_hit = true;
     }
+    is_intersecting
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::{ARENA_HEIGHT, ARENA_WIDTH};
+    use std::f32::consts::PI;
+
+    fn body(radius: f32) -> cmpt::Body {
+        cmpt::Body { radius, ..Default::default() }
+    }
+
+    fn collides(a_pos: math::Vec2, b_pos: math::Vec2) -> bool {
+        let (mut a_body, mut b_body) = (body(4.0), body(4.0));
+        let is_hit = do_collision(a_pos, &mut a_body, b_pos, &mut b_body);
+        assert_eq!(is_hit, a_body.is_hit);
+        assert_eq!(is_hit, b_body.is_hit);
+        is_hit
+    }
+
+    #[test]
+    fn inside_arena() {
+        assert!(collides(math::vec2(100.0, 100.0), math::vec2(107.0, 100.0)));
+        assert!(collides(math::vec2(100.0, 100.0), math::vec2(108.0, 100.0)));
+        assert!(!collides(math::vec2(100.0, 100.0), math::vec2(108.5, 100.0)));
+    }
+
+    #[test]
+    fn across_left_and_right_edges() {
+        assert!(collides(math::vec2(2.0, 100.0), math::vec2(ARENA_WIDTH - 3.0, 100.0)));
+        assert!(collides(math::vec2(ARENA_WIDTH - 3.0, 100.0), math::vec2(2.0, 100.0)));
+        assert!(!collides(math::vec2(4.0, 100.0), math::vec2(ARENA_WIDTH - 5.0, 100.0)));
+    }
+
+    #[test]
+    fn across_top_and_bottom_edges() {
+        assert!(collides(math::vec2(100.0, 1.0), math::vec2(100.0, ARENA_HEIGHT - 1.0)));
+        assert!(collides(math::vec2(100.0, ARENA_HEIGHT - 1.0), math::vec2(100.0, 1.0)));
+        assert!(!collides(math::vec2(100.0, 5.0), math::vec2(100.0, ARENA_HEIGHT - 5.0)));
+    }
+
+    #[test]
+    fn across_corners() {
+        let near_origin = math::vec2(1.0, 1.0);
+        assert!(collides(near_origin, math::vec2(ARENA_WIDTH - 1.0, ARENA_HEIGHT - 1.0)));
+        assert!(collides(near_origin, math::vec2(ARENA_WIDTH - 1.0, 2.0)));
+        assert!(collides(near_origin, math::vec2(2.0, ARENA_HEIGHT - 1.0)));
+        // 6 by 6 apart diagonally is farther than 8
+        assert!(!collides(
+            math::vec2(3.0, 3.0),
+            math::vec2(ARENA_WIDTH - 3.0, ARENA_HEIGHT - 3.0)
+        ));
+    }
+
+    #[test]
+    fn outside_arena() {
+        // aliens don't wrap horizontally and leave the arena before being removed
+        assert!(collides(math::vec2(-3.0, 100.0), math::vec2(ARENA_WIDTH - 2.0, 100.0)));
+        assert!(collides(math::vec2(ARENA_WIDTH + 3.0, 100.0), math::vec2(2.0, 100.0)));
+    }
+
+    #[test]
+    fn half_arena_apart() {
+        let center = math::vec2(ARENA_WIDTH / 2.0, ARENA_HEIGHT / 2.0);
+        assert!(!collides(math::vec2(0.0, 0.0), center));
+        assert!(!collides(math::vec2(0.0, ARENA_HEIGHT / 2.0), center));
+    }
+
+    fn bullet_hits_ship(offset: math::Vec2, bullet_angle: f32, ship_angle: f32) -> bool {
+        let mut ship = crate::entity::Ship::new(0, &Default::default());
+        ship.sprite.angle = ship_angle;
+        let mut bullet = crate::entity::Bullet::new(
+            ship.position + offset,
+            bullet_angle,
+            crate::entity::Owner::Player(0),
+            &Default::default(),
+        );
+        do_sprite_collision(
+            bullet.position,
+            &bullet.sprite,
+            &mut bullet.body,
+            ship.position,
+            &ship.sprite,
+            &mut ship.body,
+            true,
+        )
+    }
+
+    #[test]
+    fn bullet_misses_ship_corner_inside_hit_circle() {
+        let offset = math::vec2(3.0, 2.2);
+        assert!(collides(math::Vec2::ZERO, offset));
+        assert!(!bullet_hits_ship(offset, 0.0, 0.0));
+        assert!(bullet_hits_ship(offset, PI, 0.0));
+    }
+
+    #[test]
+    fn ship_outline_follows_sprite_angle() {
+        // on the nose, which sticks out of the hull's flat front edge
+        let offset = math::vec2(2.6, 0.0);
+        assert!(bullet_hits_ship(offset, 0.0, 0.0));
+        // turned around, the nose points away and the hull's back edge is as flat
+        assert!(!bullet_hits_ship(offset, 0.0, PI));
+    }
+
+    #[test]
+    fn bullet_crossing_a_wing_hits_ship() {
+        // level with the wing, past the hull's bottom edge
+        let offset = math::vec2(-4.0, 3.0);
+        assert!(bullet_hits_ship(offset, 0.0, 0.0));
+        assert!(!bullet_hits_ship(offset, PI, 0.0));
+    }
+
+    fn bullet_hits_still_asteroid(prev_pos: math::Vec2, pos: math::Vec2) -> bool {
+        let mut asteroid = crate::entity::Asteroid::new(
+            math::vec2(100.0, 100.0),
+            0,
+            &mut crate::rng::Rng::new(0),
+            &Default::default(),
+        );
+        asteroid.prev_position = asteroid.position;
+        let d_pos = arena::wrapped_delta(prev_pos, pos);
+        let angle = d_pos.y.atan2(d_pos.x);
+        let mut bullet = crate::entity::Bullet::new(
+            pos,
+            angle,
+            crate::entity::Owner::Player(0),
+            &Default::default(),
+        );
+        bullet.prev_position = prev_pos;
+        do_bullet_collision(
+            &mut bullet,
+            asteroid.prev_position,
+            asteroid.position,
+            &asteroid.sprite,
+            &mut asteroid.body,
+            true,
+        )
+    }
+
+    #[test]
+    fn bullet_does_not_tunnel_through_small_asteroid() {
+        assert!(bullet_hits_still_asteroid(
+            math::vec2(80.0, 100.0),
+            math::vec2(120.0, 100.0)
+        ));
+        assert!(!bullet_hits_still_asteroid(
+            math::vec2(80.0, 120.0),
+            math::vec2(120.0, 120.0)
+        ));
+        // ends before reaching it
+        assert!(!bullet_hits_still_asteroid(
+            math::vec2(40.0, 100.0),
+            math::vec2(80.0, 100.0)
+        ));
+    }
+
+    #[test]
+    fn bullet_sweep_wraps_across_edge() {
+        // the short way down across the bottom edge stops well above the asteroid
+        let prev_pos = math::vec2(100.0, ARENA_HEIGHT - 2.0);
+        assert!(!bullet_hits_still_asteroid(prev_pos, math::vec2(100.0, 60.0)));
+    }
+
+    #[test]
+    fn bounce_conserves_momentum_and_energy() {
+        let mut small = body(4.8);
+        small.velocity = math::vec2(30.0, 0.0);
+        let mut big = body(16.0);
+        big.velocity = math::vec2(-10.0, 5.0);
+        let momentum =
+            |a: &cmpt::Body, b: &cmpt::Body| a.velocity * a.mass() + b.velocity * b.mass();
+        let energy = |a: &cmpt::Body, b: &cmpt::Body| {
+            a.mass() * a.velocity.length_squared() + b.mass() * b.velocity.length_squared()
+        };
+        let (old_momentum, old_energy) = (momentum(&small, &big), energy(&small, &big));
+        // touching across the right edge
+        bounce(
+            &mut big,
+            math::vec2(10.0, 50.0),
+            &mut small,
+            math::vec2(ARENA_WIDTH - 8.0, 53.0),
+        );
+        assert!((momentum(&small, &big) - old_momentum).length() < 0.01 * old_momentum.length());
+        assert!((energy(&small, &big) - old_energy).abs() < 0.001 * old_energy);
+        assert!(small.velocity.x < 0.0);
+        // moving apart now, so they don't bounce again
+        let velocity = small.velocity;
+        bounce(
+            &mut big,
+            math::vec2(10.0, 50.0),
+            &mut small,
+            math::vec2(ARENA_WIDTH - 8.0, 53.0),
+        );
+        assert_eq!(velocity, small.velocity);
+    }
+
+    /// Player 0 fires straight through the ship of player 1.
+    fn co_op_shot_hits_other_ship(friendly_fire: bool) -> bool {
+        let config = crate::config::Config::default();
+        let mut players: Vec<_> = (0..2).map(|id| entity::Player::new(id, &config)).collect();
+        let mut ship = entity::Ship::new(1, &config);
+        ship.position = math::vec2(100.0, 100.0);
+        ship.prev_position = ship.position;
+        players[1].ship = Some(ship);
+        let mut bullet =
+            entity::Bullet::new(math::vec2(96.0, 100.0), 0.0, entity::Owner::Player(0), &config);
+        bullet.prev_position = math::vec2(90.0, 100.0);
+        let mut game = crate::Game {
+            mode: entity::GameMode {
+                co_op: true,
+                friendly_fire,
+                ..Default::default()
+            },
+            players,
+            bullets: vec![bullet],
+            config,
+            ..Default::default()
+        };
+        update(&mut game, cfg::TIME_STEP);
+        game.players[1].ship.as_ref().unwrap().body.is_hit
+    }
+
+    #[test]
+    fn friendly_fire_is_a_toggle() {
+        assert!(!co_op_shot_hits_other_ship(false));
+        assert!(co_op_shot_hits_other_ship(true));
+    }
 }
@@ -1,104 +1,220 @@
-use crate::{cfg, entity, entity::cmpt, palette};
-use macroquad::{color, math};
-use macroquad_particles as particles;
+use crate::{cfg, config::Config, entity, entity::cmpt, rng::Rng};
+use macroquad::math;
 use std::f32::consts::PI;
 
 pub fn update(game: &mut crate::Game, _dt: f32) {
-    if let Some(ship) = game.ship.as_mut().filter(|sh| sh.body.is_hit) {
+    let old_scores: Vec<_> = game.players.iter().map(|p| p.score).collect();
+    let mut killers = Vec::new();
+    for player in &mut game.players {
+        let ship = match &mut player.ship {
+            Some(ship) if ship.body.is_hit => ship,
+            _ => continue,
+        };
+        if ship.is_shielded {
+            ship.body.is_hit = false;
+            ship.body.hit_by_player = None;
+            continue;
+        }
         ship.is_destroyed = true;
-        let expl_emitter = particles::Emitter::new(ship_explosion(cfg::SHIP_EXPLOSION_COLOR));
+        if game.mode.versus {
+            killers.extend(ship.body.hit_by_player.filter(|&id| id != player.id));
+        }
+        game.last_explosion_id += 1;
         game.explosions.push(entity::Explosion {
+            id: game.last_explosion_id,
             position: ship.position,
+            prev_position: ship.position,
             body: Default::default(),
-            life_timer: expl_emitter.config.lifetime,
-            emitter: expl_emitter,
+            kind: cmpt::ExplosionKind::Ship,
+            life_timer: cfg::SHIP_EXPLOSION_LIFETIME,
         });
     }
+    for id in killers {
+        game.players[id].score += 1;
+        game.players[id].kills += 1;
+    }
     for alien in game.aliens.iter_mut().filter(|a| a.body.is_hit) {
         alien.is_destroyed = true;
-        let expl_emitter = particles::Emitter::new(ship_explosion(cfg::ALIEN_EXPLOSION_COLOR));
+        if let Some(id) = alien.body.hit_by_player {
+            game.players[id].score += game.config.aliens.score_by_kind[alien.kind as usize];
+        }
+        game.last_explosion_id += 1;
         game.explosions.push(entity::Explosion {
+            id: game.last_explosion_id,
             position: alien.position,
+            prev_position: alien.position,
             body: cmpt::Body {
-                angle: alien.body.angle,
-                speed: alien.body.speed,
+                velocity: alien.body.velocity,
                 ..Default::default()
             },
-            life_timer: expl_emitter.config.lifetime,
-            emitter: expl_emitter,
+            kind: cmpt::ExplosionKind::Alien,
+            life_timer: cfg::SHIP_EXPLOSION_LIFETIME,
         });
     }
     game.bullets.retain(|b| !b.body.is_hit);
+    if game.mode.versus {
+        // obstacles stop bullets and ships but stay whole
+        for asteroid in &mut game.asteroids {
+            asteroid.body.is_hit = false;
+        }
+    }
     let mut new_asteroids: Vec<entity::Asteroid> = Default::default();
     for asteroid in game.asteroids.iter_mut().filter(|a| a.body.is_hit) {
         asteroid.is_destroyed = true;
-        let expl_emitter = particles::Emitter::new(asteroid_explosion());
+        if let Some(id) = asteroid.body.hit_by_player {
+            game.players[id].score += game.config.asteroids.stages[asteroid.stage].score;
+        }
+        game.last_explosion_id += 1;
         game.explosions.push(entity::Explosion {
+            id: game.last_explosion_id,
             position: asteroid.position,
+            prev_position: asteroid.position,
             body: cmpt::Body {
-                angle: asteroid.body.angle,
-                speed: asteroid.body.speed * 1.5,
+                velocity: asteroid.body.velocity * 1.5,
                 ..Default::default()
             },
-            life_timer: expl_emitter.config.lifetime,
-            emitter: expl_emitter,
+            kind: cmpt::ExplosionKind::Asteroid,
+            life_timer: cfg::ASTEROID_EXPLOSION_LIFETIME,
         });
         if asteroid.stage > 0 {
-            new_asteroids.push(entity::Asteroid::new(asteroid.position, asteroid.stage - 1));
-            new_asteroids.push(entity::Asteroid::new(asteroid.position, asteroid.stage - 1));
+            new_asteroids.extend(split(asteroid, &mut game.rng, &game.config));
         }
     }
     game.asteroids.append(&mut new_asteroids);
+    let extra_life_score = game.config.ship.extra_life_score;
+    for (player, old_score) in game.players.iter_mut().zip(old_scores) {
+        player.lives += player.score / extra_life_score - old_score / extra_life_score;
+    }
 }
 
-fn asteroid_explosion() -> particles::EmitterConfig {
-    particles::EmitterConfig {
-        one_shot: true,
-        lifetime: 0.65,
-        explosiveness: 1.0,
-        amount: 8,
-        local_coords: true,
-        initial_direction: math::vec2(0.0, 1.0),
-        initial_direction_spread: 2.0 * PI,
-        initial_velocity: 60.0,
-        initial_velocity_randomness: 0.4,
-        size: 1.7,
-        size_curve: Some(particles::Curve {
-            points: vec![(0.0, 1.0), (0.85, 1.0), (1.0, 0.0)],
-            ..Default::default()
-        }),
-        shape: particles::ParticleShape::Circle { subdivisions: 7 },
-        colors_curve: particles::ColorCurve {
-            start: palette::LIGHTGRAY,
-            mid: palette::LIGHTGRAY,
-            end: palette::LIGHTGRAY,
-        },
-        ..Default::default()
-    }
+/// Children share the parent's velocity and fly apart across the impact, as set by
+/// [`crate::config::AsteroidConfig::split`]. Without a bullet to go by, the split direction is random.
+fn split(asteroid: &entity::Asteroid, rng: &mut Rng, config: &Config) -> [entity::Asteroid; 2] {
+    let rule = &config.asteroids.split;
+    let stage = asteroid.stage - 1;
+    let parent_velocity = asteroid.body.velocity;
+    let (impact_angle, impact_speed) = match asteroid.body.impact_angle {
+        Some(angle) => (angle, rule.impact_speed),
+        None => (rng.gen_range(0.0, 2.0 * PI), 0.0),
+    };
+    let impact_direction = math::vec2(impact_angle.cos(), impact_angle.sin());
+    let velocity = parent_velocity * rule.inherited_velocity + impact_direction * impact_speed;
+    let spread = impact_direction.perp()
+        * rule.spread
+        * config.asteroids.stages[stage].max_speed
+        * rng.gen_range(0.5, 1.0);
+    [spread, -spread].map(|spread| {
+        let mut child = entity::Asteroid::new(asteroid.position, stage, rng, config);
+        child.body.velocity = velocity + spread;
+        child
+    })
 }
 
-fn ship_explosion(color: color::Color) -> particles::EmitterConfig {
-    particles::EmitterConfig {
-        one_shot: true,
-        lifetime: 1.5,
-        explosiveness: 1.0,
-        amount: 8,
-        local_coords: true,
-        initial_direction: math::vec2(0.0, 1.0),
-        initial_direction_spread: 2.0 * PI,
-        initial_velocity: 20.0,
-        initial_velocity_randomness: 0.4,
-        size: 2.0,
-        size_curve: Some(particles::Curve {
-            points: vec![(0.0, 1.0), (0.85, 1.0), (1.0, 0.0)],
-            ..Default::default()
-        }),
-        shape: particles::ParticleShape::Circle { subdivisions: 4 },
-        colors_curve: particles::ColorCurve {
-            start: color,
-            mid: color,
-            end: color,
-        },
-        ..Default::default()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::running_game;
+
+    /// Puts a still asteroid of the last stage, which doesn't split, at `position`.
+    fn add_asteroid(game: &mut crate::Game, position: math::Vec2) {
+        let mut asteroid = entity::Asteroid::new(position, 0, &mut game.rng, &game.config);
+        asteroid.body.velocity = math::Vec2::ZERO;
+        game.asteroids.push(asteroid);
+    }
+
+    /// Puts a still alien at `position`.
+    fn add_alien(game: &mut crate::Game, position: math::Vec2) -> cmpt::AlienKind {
+        let mut alien = entity::Alien::new(&mut game.rng, &game.config);
+        alien.position = position;
+        alien.prev_position = position;
+        alien.body.velocity = math::Vec2::ZERO;
+        let kind = alien.kind;
+        game.aliens.push(alien);
+        kind
+    }
+
+    fn add_bullet(game: &mut crate::Game, position: math::Vec2, owner: entity::Owner) {
+        game.bullets
+            .push(entity::Bullet::new(position, 0.0, owner, &game.config));
+    }
+
+    #[test]
+    fn only_the_ships_own_bullets_score() {
+        let (mut game, no_inputs) = running_game(Default::default());
+        let ship_position = game.players[0].ship.as_ref().unwrap().position;
+        let shot = ship_position + math::vec2(0.0, 60.0);
+        let alien_shot = ship_position - math::vec2(0.0, 60.0);
+        add_asteroid(&mut game, shot);
+        add_bullet(&mut game, shot, entity::Owner::Player(0));
+        add_asteroid(&mut game, alien_shot);
+        let kind = add_alien(&mut game, alien_shot + math::vec2(0.0, -60.0));
+        add_bullet(&mut game, alien_shot, entity::Owner::Alien(kind));
+        // both rammed by the ship
+        add_asteroid(&mut game, ship_position);
+        add_alien(&mut game, ship_position);
+        game.step_players(&no_inputs);
+        assert!(game.asteroids.is_empty());
+        assert_eq!(game.aliens.len(), 1);
+        assert!(game.players[0].ship.is_none());
+        assert_eq!(game.players[0].score, game.config.asteroids.stages[0].score);
+    }
+
+    #[test]
+    fn extra_life_for_every_threshold_crossed() {
+        // extra life score, score before the kill, lives gained
+        for (extra_life_score, score, gained) in [(10000, 9950, 1), (10000, 9850, 0), (30, 25, 4)] {
+            let (mut game, no_inputs) = running_game(Default::default());
+            game.config.ship.extra_life_score = extra_life_score;
+            game.players[0].score = score;
+            let lives = game.players[0].lives;
+            let shot = math::vec2(cfg::ARENA_WIDTH / 2.0, cfg::ARENA_HEIGHT / 4.0);
+            add_asteroid(&mut game, shot);
+            add_bullet(&mut game, shot, entity::Owner::Player(0));
+            game.step_players(&no_inputs);
+            assert_eq!(game.players[0].score, score + game.config.asteroids.stages[0].score);
+            assert_eq!(game.players[0].lives, lives + gained);
+        }
+    }
+
+    #[test]
+    fn co_op_kills_credit_only_the_shooter() {
+        let mode = entity::GameMode { co_op: true, ..Default::default() };
+        let (mut game, no_inputs) = running_game(mode);
+        let center = math::vec2(cfg::ARENA_WIDTH / 2.0, cfg::ARENA_HEIGHT / 4.0);
+        let (asteroid_shot, alien_shot) = (center - math::vec2(30.0, 0.0), center);
+        add_asteroid(&mut game, asteroid_shot);
+        add_bullet(&mut game, asteroid_shot, entity::Owner::Player(1));
+        let kind = add_alien(&mut game, alien_shot);
+        add_bullet(&mut game, alien_shot, entity::Owner::Player(1));
+        game.step_players(&no_inputs);
+        assert!(game.asteroids.is_empty() && game.aliens.is_empty());
+        let score =
+            game.config.asteroids.stages[0].score + game.config.aliens.score_by_kind[kind as usize];
+        assert_eq!(game.players[1].score, score);
+        assert_eq!(game.players[0].score, 0);
+    }
+
+    #[test]
+    fn split_children_inherit_velocity_and_spread_across_the_impact() {
+        let config = Config::default();
+        let mut rng = Rng::new(1);
+        let position = math::vec2(100.0, 100.0);
+        let mut parent = entity::Asteroid::new(position, 2, &mut rng, &config);
+        parent.body.velocity = math::vec2(10.0, -20.0);
+        parent.body.impact_angle = Some(PI / 2.0);
+        let children = split(&parent, &mut rng, &config);
+        let rule = &config.asteroids.split;
+        let impact_direction = math::vec2(0.0, 1.0);
+        let velocity =
+            parent.body.velocity * rule.inherited_velocity + impact_direction * rule.impact_speed;
+        let max_spread = rule.spread * config.asteroids.stages[1].max_speed;
+        for child in &children {
+            assert_eq!((child.stage, child.position), (1, position));
+            let spread = child.body.velocity - velocity;
+            assert!(spread.dot(impact_direction).abs() < 1e-3);
+            assert!((0.5 * max_spread..=max_spread).contains(&spread.length()));
+        }
+        let [a, b] = children.map(|child| child.body.velocity - velocity);
+        assert!((a + b).length() < 1e-3);
     }
 }
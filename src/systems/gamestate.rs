@@ -4,43 +4,197 @@ pub fn update(game: &mut crate::Game, _dt: f32) {
     // dbg!(&game.state);
     match game.state {
         entity::GameState::Pause => {
-            if game.player_actions.contains(&entity::Action::TogglePause) {
+            if is_pause_toggled(game) {
                 game.state = entity::GameState::LevelRunning;
             }
         }
         entity::GameState::LevelLoading => {
-            if game.ship.is_some() && !game.asteroids.is_empty() {
+            // a versus round may be played without obstacles
+            let are_asteroids_ready = game.mode.versus || !game.asteroids.is_empty();
+            if game.ships().next().is_some() && are_asteroids_ready {
+                if game.mode.versus {
+                    game.break_timer = game.config.versus.round_start_time;
+                    game.state = entity::GameState::RoundStarting;
+                } else {
+                    game.state = entity::GameState::LevelRunning;
+                }
+            }
+        }
+        entity::GameState::RoundStarting => {
+            if game.break_timer == 0.0 {
                 game.state = entity::GameState::LevelRunning;
             }
         }
         entity::GameState::LevelRunning => {
-            if game.player_actions.contains(&entity::Action::TogglePause) {
+            if is_pause_toggled(game) {
                 game.state = entity::GameState::Pause;
             }
-            if game.ship.as_ref().map_or(false, |sh| sh.is_destroyed) {
+            let mut is_ship_lost = false;
+            for player in &mut game.players {
+                if player.ship.as_ref().is_some_and(|sh| sh.is_destroyed) {
+                    is_ship_lost = true;
+                    // versus ships come back until the round is won
+                    if !game.mode.versus {
+                        player.lives -= 1;
+                    }
+                    player.respawn_timer = game.config.ship.respawn_delay;
+                }
+            }
+            if game.mode.versus {
+                let target_score = game.config.versus.target_score;
+                let mut winners = game.players.iter_mut().filter(|p| p.score >= target_score);
+                match (winners.next(), winners.next()) {
+                    (Some(winner), None) => {
+                        winner.rounds_won += 1;
+                        game.state = if winner.rounds_won >= game.config.versus.rounds_to_win {
+                            entity::GameState::MatchOver
+                        } else {
+                            entity::GameState::RoundOver
+                        };
+                        game.break_timer = game.config.versus.round_over_time;
+                    }
+                    // both got there on the same step, the round is played again
+                    (Some(_), Some(_)) => {
+                        game.state = entity::GameState::RoundOver;
+                        game.break_timer = game.config.versus.round_over_time;
+                    }
+                    _ => (),
+                }
+            } else if game.players.iter().all(|p| p.lives == 0) {
                 game.break_timer = 2.0;
                 game.state = entity::GameState::GameOver;
-            }
-            if game.asteroids.is_empty() && game.aliens.is_empty() {
+            } else if !is_ship_lost && game.asteroids.is_empty() && game.aliens.is_empty() {
+                game.wave += 1;
                 game.break_timer = 2.0;
                 game.state = entity::GameState::LevelCompleted;
             }
         }
         entity::GameState::LevelCompleted => {
             if game.break_timer == 0.0 {
-                let old_game = std::mem::replace(game, Default::default());
-                game.renderer = old_game.renderer;
-                game.alien_timer = old_game.alien_timer;
-                game.star_bg = old_game.star_bg;
+                clear_arena(game);
+            }
+        }
+        entity::GameState::RoundOver => {
+            if game.break_timer == 0.0 {
+                clear_arena(game);
+                for player in &mut game.players {
+                    player.score = 0;
+                }
             }
         }
         entity::GameState::GameOver => {
             if game.break_timer == 0.0 {
-                let old_game = std::mem::replace(game, Default::default());
-                game.renderer = old_game.renderer;
-                game.alien_timer = old_game.alien_timer;
-                game.star_bg = old_game.star_bg;
+                new_game(game);
             }
         }
+        entity::GameState::MatchOver => {
+            // the results stay up for a while before a rematch can be asked for
+            if game.break_timer == 0.0 && is_pause_toggled(game) {
+                new_game(game);
+            }
+        }
+    }
+}
+
+/// Starts loading the next level or round, the players keep their scores.
+fn clear_arena(game: &mut crate::Game) {
+    let old_game = std::mem::take(game);
+    game.mode = old_game.mode;
+    game.config = old_game.config;
+    game.rng = old_game.rng;
+    game.alien_timer = old_game.alien_timer;
+    game.players = old_game.players;
+    for player in &mut game.players {
+        player.ship = None;
+        player.respawn_timer = 0.0;
+    }
+    game.wave = old_game.wave;
+}
+
+fn new_game(game: &mut crate::Game) {
+    let old_game = std::mem::take(game);
+    game.mode = old_game.mode;
+    game.config = old_game.config;
+    game.rng = old_game.rng;
+    game.alien_timer = old_game.alien_timer;
+}
+
+fn is_pause_toggled(game: &crate::Game) -> bool {
+    game.players
+        .iter()
+        .any(|p| p.actions.contains(&entity::Action::TogglePause))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cfg, config::Config, systems::running_game};
+    use macroquad::math;
+    use std::f32::consts::PI;
+
+    fn running_versus_game() -> (crate::Game, Vec<entity::PlayerInput>) {
+        running_game(entity::GameMode { versus: true, ..Default::default() })
+    }
+
+    /// Has the ship of `shooter` fire a bullet that hits the other player's ship on the
+    /// next step, and puts the shooter one kill away from the target score.
+    fn fire_winning_shot(game: &mut crate::Game, shooter: usize) {
+        game.players[shooter].score = game.config.versus.target_score - 1;
+        let target = game.players[1 - shooter].ship.as_ref().unwrap().position;
+        // reaches the ship's center in one step, flying towards it from its side
+        let (angle, side) = if target.x > cfg::ARENA_WIDTH / 2.0 {
+            (0.0, -1.0)
+        } else {
+            (PI, 1.0)
+        };
+        let travel = math::vec2(cfg::TIME_STEP * game.config.bullets.ship_speed, 0.0);
+        let owner = entity::Owner::Player(shooter);
+        let bullet = entity::Bullet::new(target + travel * side, angle, owner, &game.config);
+        game.bullets.push(bullet);
+    }
+
+    #[test]
+    fn asteroids_are_placed_when_no_spot_is_clear_of_the_ships() {
+        let mut config = Config::default();
+        // unreachable anywhere on the arena, bypassing validation
+        config.ship.safe_radius = 250.0;
+        let mut game = crate::Game {
+            mode: entity::GameMode { versus: true, ..Default::default() },
+            ..crate::Game::new(1, config)
+        };
+        let no_inputs = vec![entity::PlayerInput::default(); 2];
+        game.step_players(&no_inputs);
+        assert_eq!(game.asteroids.len(), game.config.versus.asteroids);
+        game.step_players(&no_inputs);
+        assert!(matches!(game.state, entity::GameState::RoundStarting));
+    }
+
+    #[test]
+    fn versus_kill_scores_and_wins_the_round() {
+        let (mut game, no_inputs) = running_versus_game();
+        fire_winning_shot(&mut game, 0);
+        game.step_players(&no_inputs);
+        assert!(matches!(game.state, entity::GameState::RoundOver));
+        assert_eq!(game.players[0].score, game.config.versus.target_score);
+        assert_eq!((game.players[0].kills, game.players[0].rounds_won), (1, 1));
+        while !matches!(game.state, entity::GameState::RoundStarting) {
+            game.step_players(&no_inputs);
+        }
+        assert_eq!(game.players[0].score, 0);
+        assert_eq!(game.players[0].rounds_won, 1);
+    }
+
+    #[test]
+    fn versus_kills_on_the_same_step_replay_the_round() {
+        let (mut game, no_inputs) = running_versus_game();
+        fire_winning_shot(&mut game, 0);
+        fire_winning_shot(&mut game, 1);
+        game.step_players(&no_inputs);
+        assert!(matches!(game.state, entity::GameState::RoundOver));
+        assert!(game.players.iter().all(|p| p.rounds_won == 0));
+        while !matches!(game.state, entity::GameState::RoundStarting) {
+            game.step_players(&no_inputs);
+        }
+        assert!(game.players.iter().all(|p| p.score == 0));
     }
 }
@@ -2,9 +2,28 @@ pub mod ai;
 pub mod cleanup;
 pub mod collision;
 pub mod damage;
-pub mod draw;
 pub mod gamestate;
-pub mod input;
 pub mod moving;
 pub mod spawn;
 pub mod timers;
+
+/// A game in its first level or round, with the asteroids cleared away and the ships open
+/// to hits, and inputs for its players that hold nothing.
+#[cfg(test)]
+pub(crate) fn running_game(
+    mode: crate::entity::GameMode,
+) -> (crate::Game, Vec<crate::entity::PlayerInput>) {
+    let mut game = crate::Game {
+        mode,
+        ..crate::Game::new(1, Default::default())
+    };
+    let no_inputs = vec![Default::default(); mode.player_count()];
+    while !matches!(game.state, crate::entity::GameState::LevelRunning) {
+        game.step_players(&no_inputs);
+    }
+    game.asteroids.clear();
+    for ship in game.players.iter_mut().filter_map(|p| p.ship.as_mut()) {
+        ship.invulnerability_timer = 0.0;
+    }
+    (game, no_inputs)
+}
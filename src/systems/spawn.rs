@@ -1,52 +1,170 @@
-use crate::{cfg, entity};
-use macroquad::{math, rand};
+use crate::{arena, cfg, config::Config, entity, rng::Rng};
+use macroquad::math;
+use std::f32::consts::PI;
+
+const MAX_PLACEMENT_ATTEMPTS: u32 = 1000;
 
 pub fn update(game: &mut crate::Game, _dt: f32) {
     match game.state {
         entity::GameState::LevelLoading => {
-            if game.ship.is_none() {
-                game.ship = Some(entity::Ship::new());
+            if game.players.is_empty() {
+                game.players = (0..game.mode.player_count())
+                    .map(|id| entity::Player::new(id, &game.config))
+                    .collect();
+            }
+            for player in game.players.iter_mut().filter(|p| p.lives > 0) {
+                if player.ship.is_none() {
+                    player.ship = Some(new_ship(player.id, game.mode, &game.config));
+                }
             }
             if game.asteroids.is_empty() {
-                let ship = game.ship.as_ref().unwrap();
-                let start_stage = cfg::ASTEROID_STAGES.len() - 1;
-                while game.asteroids.len() < 5 {
-                    let rand_pos = math::vec2(
-                        rand::gen_range(0.0, cfg::ARENA_WIDTH),
-                        rand::gen_range(0.0, cfg::ARENA_HEIGHT),
-                    );
-                    let delta_pos = rand_pos - ship.position;
-                    const RADIUS: f32 = cfg::ARENA_HEIGHT * 0.3;
-                    let is_too_close = delta_pos.x.powi(2) + delta_pos.y.powi(2) <= RADIUS.powi(2);
-                    if !is_too_close {
-                        game.asteroids
-                            .push(entity::Asteroid::new(rand_pos, start_stage));
-                    }
+                let start_stage = game.config.asteroids.stages.len() - 1;
+                let extra_asteroids = game.wave as usize - 1;
+                let asteroid_count = if game.mode.versus {
+                    game.config.versus.asteroids
+                } else {
+                    usize::min(
+                        game.config.waves.start_asteroids + extra_asteroids,
+                        game.config.waves.max_asteroids,
+                    )
+                };
+                let speed_factor = 1.0 + game.config.waves.speed_step * (game.wave - 1) as f32;
+                let ship_positions: Vec<_> = game
+                    .players
+                    .iter()
+                    .filter_map(|p| p.ship.as_ref())
+                    .map(|sh| sh.position)
+                    .collect();
+                for _ in 0..asteroid_count {
+                    let position = asteroid_position(&ship_positions, &mut game.rng, &game.config);
+                    let mut asteroid =
+                        entity::Asteroid::new(position, start_stage, &mut game.rng, &game.config);
+                    asteroid.body.velocity *= speed_factor;
+                    game.asteroids.push(asteroid);
                 }
             }
         }
         entity::GameState::LevelRunning => {
-            if let Some(ship) = &mut game.ship {
-                let shoot_is_ready = ship.weapon_cooldown_timer == 0.0;
-                if game.player_actions.contains(&entity::Action::Shoot) && shoot_is_ready {
-                    ship.weapon_cooldown_timer = cfg::BULLET_COOLDOWN;
-                    let bullet_offset = math::vec2(
-                        ship.sprite.angle.cos() * cfg::SHIP_HIT_RADIUS,
-                        ship.sprite.angle.sin() * cfg::SHIP_HIT_RADIUS,
-                    );
-                    game.bullets.push(entity::Bullet::new(
-                        ship.position + bullet_offset,
-                        ship.sprite.angle,
-                        None,
-                    ));
+            for player in &mut game.players {
+                if player.ship.is_none() && player.lives > 0 && player.respawn_timer == 0.0 {
+                    let mut ship = new_ship(player.id, game.mode, &game.config);
+                    let is_clear = game
+                        .asteroids
+                        .iter()
+                        .all(|a| !is_too_close(a.position, ship.position, &game.config));
+                    if is_clear {
+                        ship.invulnerability_timer = game.config.ship.invulnerability_time;
+                        player.ship = Some(ship);
+                    }
+                }
+                if let Some(ship) = &mut player.ship {
+                    let shoot_is_ready =
+                        ship.weapon_cooldown_timer == 0.0 && !ship.is_in_hyperspace;
+                    if player.actions.contains(&entity::Action::Shoot) && shoot_is_ready {
+                        ship.weapon_cooldown_timer = game.config.bullets.cooldown;
+                        let bullet_offset = math::vec2(
+                            ship.sprite.angle.cos() * game.config.ship.hit_radius,
+                            ship.sprite.angle.sin() * game.config.ship.hit_radius,
+                        );
+                        game.bullets.push(entity::Bullet::new(
+                            ship.position + bullet_offset,
+                            ship.sprite.angle,
+                            entity::Owner::Player(player.id),
+                            &game.config,
+                        ));
+                    }
                 }
             }
-            let time_to_spawn_alien = game.alien_timer == 0.0;
+            let time_to_spawn_alien = game.alien_timer == 0.0 && !game.mode.versus;
             if time_to_spawn_alien {
-                game.alien_timer = cfg::ALIEN_SPAWN_PERIOD;
-                game.aliens.push(entity::Alien::new());
+                game.alien_timer = f32::max(
+                    game.config.aliens.spawn_period
+                        * game
+                            .config
+                            .waves
+                            .alien_period_factor
+                            .powi(game.wave as i32 - 1),
+                    game.config.aliens.min_spawn_period,
+                );
+                game.aliens
+                    .push(entity::Alien::new(&mut game.rng, &game.config));
             }
         }
         _ => (),
     }
 }
+
+/// Ships start side by side around the center, one per player. In versus they start
+/// farther apart, facing each other.
+fn new_ship(player_id: usize, mode: entity::GameMode, config: &Config) -> entity::Ship {
+    let mut ship = entity::Ship::new(player_id, config);
+    let gap = if mode.versus {
+        cfg::ARENA_WIDTH / 2.0
+    } else {
+        cfg::ARENA_WIDTH / 6.0
+    };
+    let player_count = mode.player_count();
+    ship.position.x += gap * (player_id as f32 - (player_count - 1) as f32 / 2.0);
+    ship.prev_position = ship.position;
+    if mode.versus && player_id % 2 == 1 {
+        ship.sprite.angle = PI;
+    }
+    ship
+}
+
+/// Picks a random spot outside the safe radius of every ship. When none of the first
+/// [`MAX_PLACEMENT_ATTEMPTS`] spots is clear, falls back to the one farthest from them.
+fn asteroid_position(ship_positions: &[math::Vec2], rng: &mut Rng, config: &Config) -> math::Vec2 {
+    let mut farthest = (f32::NEG_INFINITY, math::Vec2::ZERO);
+    for _ in 0..MAX_PLACEMENT_ATTEMPTS {
+        let rand_pos =
+            math::vec2(rng.gen_range(0.0, cfg::ARENA_WIDTH), rng.gen_range(0.0, cfg::ARENA_HEIGHT));
+        if ship_positions
+            .iter()
+            .all(|&ship_pos| !is_too_close(rand_pos, ship_pos, config))
+        {
+            return rand_pos;
+        }
+        let clearance = ship_positions
+            .iter()
+            .map(|&ship_pos| arena::wrapped_delta(ship_pos, rand_pos).length())
+            .fold(f32::INFINITY, f32::min);
+        if clearance > farthest.0 {
+            farthest = (clearance, rand_pos);
+        }
+    }
+    farthest.1
+}
+
+fn is_too_close(position: math::Vec2, ship_position: math::Vec2, config: &Config) -> bool {
+    let delta_pos = arena::wrapped_delta(ship_position, position);
+    delta_pos.x.powi(2) + delta_pos.y.powi(2) <= config.ship.safe_radius.powi(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::running_game;
+
+    #[test]
+    fn respawn_waits_until_no_asteroid_is_within_the_safe_radius() {
+        let (mut game, no_inputs) = running_game(Default::default());
+        game.players[0].ship = None;
+        let spawn_position = new_ship(0, game.mode, &game.config).position;
+        let offset = math::vec2(game.config.ship.safe_radius - 2.0, 0.0);
+        let mut asteroid =
+            entity::Asteroid::new(spawn_position + offset, 0, &mut game.rng, &game.config);
+        // drifting away from where the ship comes back
+        asteroid.body.velocity = math::vec2(game.config.asteroids.stages[0].max_speed, 0.0);
+        game.asteroids.push(asteroid);
+        let mut waited_steps = 0;
+        while is_too_close(game.asteroids[0].position, spawn_position, &game.config) {
+            assert!(game.players[0].ship.is_none());
+            game.step_players(&no_inputs);
+            waited_steps += 1;
+        }
+        assert!(waited_steps > 1);
+        let ship = game.players[0].ship.as_ref().unwrap();
+        assert!(ship.invulnerability_timer > 0.0);
+    }
+}
@@ -6,8 +6,21 @@ pub fn update(game: &mut crate::Game, dt: f32) {
         _ => {
             game.alien_timer = f32::max(0.0, game.alien_timer - dt);
             game.break_timer = f32::max(0.0, game.break_timer - dt);
-            if let Some(ship) = &mut game.ship {
+            for player in &mut game.players {
+                player.respawn_timer = f32::max(0.0, player.respawn_timer - dt);
+            }
+            for ship in game.players.iter_mut().filter_map(|p| p.ship.as_mut()) {
                 ship.weapon_cooldown_timer = f32::max(0.0, ship.weapon_cooldown_timer - dt);
+                ship.invulnerability_timer = f32::max(0.0, ship.invulnerability_timer - dt);
+                ship.hyperspace_timer = f32::max(0.0, ship.hyperspace_timer - dt);
+                ship.shield_energy = if ship.is_shielded {
+                    f32::max(0.0, ship.shield_energy - game.config.shield.drain_speed * dt)
+                } else {
+                    f32::min(
+                        game.config.shield.max_energy,
+                        ship.shield_energy + game.config.shield.recharge_speed * dt,
+                    )
+                };
             }
             for explosion in &mut game.explosions {
                 explosion.life_timer = f32::max(0.0, explosion.life_timer - dt);
@@ -0,0 +1,37 @@
+//! Re-reads the config file while the game runs, so handling can be tuned by feel.
+
+use asteroids::config;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    /// Starts from the file as it is now; a missing file is picked up once it's created.
+    pub fn new(path: PathBuf) -> Self {
+        let modified = modified(&path);
+        ConfigWatcher { path, modified }
+    }
+
+    /// Gives the freshly read config after the file changed, `None` while it stays the same.
+    /// Looking at the modification time is cheap enough to do every frame.
+    pub fn poll(&mut self) -> Option<Result<config::Config, String>> {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        // a deleted file keeps the values the game runs with
+        modified.map(|_| crate::read_config(&self.path))
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}